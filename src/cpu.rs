@@ -1,11 +1,59 @@
-use crate::ram::{LETTER_SIZE, RAM, RAM_OFFSET};
-use rand::Rng;
+use std::error::Error;
+use std::fmt;
+use crate::ram::{LARGE_LETTERS_OFFSET, LARGE_LETTER_SIZE, LETTER_SIZE, RAM, RAM_OFFSET};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 use crate::drivers::Display;
+use crate::quirks::Quirks;
+use crate::save_state::{StateReader, StateWriter};
 
 const STACK_SIZE: usize = 0x10;
 // 16
 const SPECIAL_REGISTER: usize = 0xF;
+// the delay and sound timers count down at 60 Hz
+pub const TIMER_FREQUENCY: u32 = 60;
+// XO-CHIP F000 nnnn, the only instruction followed by a second word
+const LONG_LOAD_OPCODE: u16 = 0xF000;
+// stored in save states when Fx0A isn't waiting for a release
+const NO_KEY: u8 = 0xFF;
+// pitch register value for which the audio pattern plays at 4000 bits per second
+pub const DEFAULT_PITCH_REGISTER: u8 = 64;
+
+#[derive(Debug, PartialEq)]
+pub enum StepOutcome {
+    // the instruction executed and the program continues
+    Continue,
+    // the program asked to stop
+    Exit,
+}
+
+// everything that can go wrong while executing a single instruction, pc is the address of that instruction
+#[derive(Debug, PartialEq)]
+pub enum CpuError {
+    InvalidOpcode { pc: usize, opcode: u16 },
+    StackOverflow { pc: usize },
+    StackUnderflow { pc: usize },
+    MemoryOutOfBounds { pc: usize, address: usize },
+    InvalidKey { pc: usize, key: u8 },
+    DeviceMissing { pc: usize, device: &'static str },
+}
 
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CpuError::InvalidOpcode { pc, opcode } => write!(f, "Invalid instruction {:04X} at {:#05X}", opcode, pc),
+            CpuError::StackOverflow { pc } => write!(f, "Stack overflow, max call stack is {} at {:#05X}", STACK_SIZE, pc),
+            CpuError::StackUnderflow { pc } => write!(f, "Stack underflow at {:#05X}", pc),
+            CpuError::MemoryOutOfBounds { pc, address } => write!(f, "Memory access out of bounds at address {:#05X} at {:#05X}", address, pc),
+            CpuError::InvalidKey { pc, key } => write!(f, "Invalid key supplied; {} at {:#05X}", key, pc),
+            CpuError::DeviceMissing { pc, device } => write!(f, "No {} available for the instruction at {:#05X}", device, pc),
+        }
+    }
+}
+
+impl Error for CpuError {}
+
+#[derive(Clone)]
 pub struct CPU {
     registers: [u8; 16],
     // position in memory
@@ -14,25 +62,158 @@ pub struct CPU {
     stack_pointer: usize,
     // special register used for memory addresses mainly
     i: u16,
-    //TODO: add the time and sound registers, decreasing at 60 Hz
+    // timer registers, decreasing at 60 Hz until they reach 0
+    delay_timer: u8,
+    sound_timer: u8,
+    // behavior of the instructions that differ between interpreters
+    quirks: Quirks,
+    // SUPER-CHIP user flags, on the HP-48 these were stored in the RPL registers of the calculator
+    rpl_flags: [u8; 16],
+    // XO-CHIP 1-bit audio samples that are played instead of the buzzer once loaded, and their playback rate
+    audio_pattern: Option<[u8; 16]>,
+    pitch: u8,
+    // keys of the previous instruction, Fx0A only accepts keys that weren't held before it started waiting
+    previous_keys: [bool; 16],
+    // the key Fx0A saw being pressed, it finishes once the key is released
+    awaited_key: Option<u8>,
+    // CXKK draws from a seeded generator so runs can be reproduced. ChaCha produces the same numbers on every
+    // platform and rand version, and its position in the stream can be saved
+    seed: u64,
+    rng: ChaCha8Rng,
+}
+
+impl Default for CPU {
+    fn default() -> Self {
+        CPU::new()
+    }
 }
 
 impl CPU {
     pub fn new() -> Self {
+        let seed = rand::random();
         CPU {
             program_counter: RAM_OFFSET,
             registers: [0; 16],
             stack: [0; STACK_SIZE],
             stack_pointer: 0,
             i: 0x0,
+            delay_timer: 0,
+            sound_timer: 0,
+            quirks: Quirks::default(),
+            rpl_flags: [0; 16],
+            audio_pattern: None,
+            pitch: DEFAULT_PITCH_REGISTER,
+            previous_keys: [false; 16],
+            awaited_key: None,
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
         }
     }
 
-    fn read_opcode(&self, ram: &RAM) -> u16 {
+    // writes everything except the configuration, quirks come from the command line
+    pub(crate) fn save_state(&self, writer: &mut StateWriter) {
+        for value in self.registers {
+            writer.write_u8(value);
+        }
+        writer.write_u32(self.program_counter as u32);
+        for value in self.stack {
+            writer.write_u16(value);
+        }
+        writer.write_u8(self.stack_pointer as u8);
+        writer.write_u16(self.i);
+        writer.write_u8(self.delay_timer);
+        writer.write_u8(self.sound_timer);
+        for value in self.rpl_flags {
+            writer.write_u8(value);
+        }
+        match &self.audio_pattern {
+            Some(pattern) => {
+                writer.write_u8(1);
+                for value in pattern {
+                    writer.write_u8(*value);
+                }
+            }
+            None => writer.write_u8(0)
+        }
+        writer.write_u8(self.pitch);
+        for pressed in self.previous_keys {
+            writer.write_u8(pressed as u8);
+        }
+        writer.write_u8(self.awaited_key.unwrap_or(NO_KEY));
+        writer.write_u64(self.seed);
+        let word_pos = self.rng.get_word_pos();
+        writer.write_u64(word_pos as u64);
+        writer.write_u64((word_pos >> 64) as u64);
+    }
+
+    pub(crate) fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        for value in self.registers.iter_mut() {
+            *value = reader.read_u8()?;
+        }
+        self.program_counter = reader.read_u32()? as usize;
+        for value in self.stack.iter_mut() {
+            *value = reader.read_u16()?;
+        }
+        self.stack_pointer = reader.read_u8()? as usize;
+        if self.stack_pointer > STACK_SIZE {
+            return Err(format!("Invalid stack pointer {} in save state", self.stack_pointer));
+        }
+        self.i = reader.read_u16()?;
+        self.delay_timer = reader.read_u8()?;
+        self.sound_timer = reader.read_u8()?;
+        for value in self.rpl_flags.iter_mut() {
+            *value = reader.read_u8()?;
+        }
+        self.audio_pattern = match reader.read_u8()? {
+            0 => None,
+            _ => {
+                let mut pattern = [0; 16];
+                for value in pattern.iter_mut() {
+                    *value = reader.read_u8()?;
+                }
+                Some(pattern)
+            }
+        };
+        self.pitch = reader.read_u8()?;
+        for pressed in self.previous_keys.iter_mut() {
+            *pressed = reader.read_u8()? != 0;
+        }
+        self.awaited_key = match reader.read_u8()? {
+            NO_KEY => None,
+            key if key < 16 => Some(key),
+            key => return Err(format!("Invalid key {} in save state", key))
+        };
+        self.set_seed(reader.read_u64()?);
+        let word_pos = reader.read_u64()? as u128 | (reader.read_u64()? as u128) << 64;
+        self.rng.set_word_pos(word_pos);
+        Ok(())
+    }
+
+    fn read_opcode(&self, ram: &RAM) -> Result<u16, CpuError> {
         let p = self.program_counter;
+        if p + 1 >= ram.size() {
+            return Err(CpuError::MemoryOutOfBounds { pc: p, address: p + 1 });
+        }
         let op_byte1 = ram.get(p) as u16;
         let op_byte2 = ram.get(p + 1) as u16;
-        op_byte1 << 8 | op_byte2
+        Ok(op_byte1 << 8 | op_byte2)
+    }
+
+    // address of the instruction that is currently executing
+    fn instruction_address(&self) -> usize {
+        self.program_counter - 2
+    }
+
+    // make sure that length bytes starting at address can be accessed
+    fn check_memory(&self, ram: &RAM, address: usize, length: usize) -> Result<(), CpuError> {
+        if address + length > ram.size() {
+            return Err(CpuError::MemoryOutOfBounds { pc: self.instruction_address(), address: address + length - 1 });
+        }
+        Ok(())
+    }
+
+    fn require<T>(&self, device: Option<T>, name: &'static str) -> Result<T, CpuError> {
+        device.ok_or(CpuError::DeviceMissing { pc: self.instruction_address(), device: name })
     }
     // convenience functions
     pub fn set_register(&mut self, nr: usize, value: u8) {
@@ -43,10 +224,50 @@ impl CPU {
         self.registers[nr]
     }
 
+    // restarts the random numbers
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    pub fn audio_pattern(&self) -> Option<&[u8; 16]> {
+        self.audio_pattern.as_ref()
+    }
+
+    pub fn pitch(&self) -> u8 {
+        self.pitch
+    }
+
+    // decrease both timers by a single 60 Hz step, called once per frame independent of how many instructions ran
+    pub fn decrement_timers(&mut self) {
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        self.sound_timer = self.sound_timer.saturating_sub(1);
+    }
+
     // run all RAM instructions
-    pub fn tick(&mut self, ram: &mut RAM, keypad: Option<&[bool; 16]>, display: Option<&mut Display>) -> bool {
-        let opcode = self.read_opcode(ram);
+    pub fn tick(&mut self, ram: &mut RAM, keypad: Option<&[bool; 16]>, display: Option<&mut dyn Display>) -> Result<StepOutcome, CpuError> {
+        let opcode = self.read_opcode(ram)?;
         self.program_counter += 2;
+        let previous_keys = self.previous_keys;
+        if let Some(keypad) = keypad {
+            self.previous_keys = *keypad;
+        }
 
         // opcode group
         let c = ((opcode & 0xF000) >> 12) as u8;
@@ -61,14 +282,24 @@ impl CPU {
         let kk = (opcode & 0x00FF) as u8;
 
         match (c, x, y, d) {
-            (0, 0, 0, 0) => { return false; }
-            (0, 0, 0, 0xE) => self.clear_display(display.unwrap()),
-            (0, 0, 0xE, 0xE) => self.ret(),
+            (0, 0, 0, 0) => { return Ok(StepOutcome::Exit); }
+            (0, 0, 0, 0xE) => self.clear_display(self.require(display, "display")?),
+            // the standard encoding used by binary ROMs, 000E is the one of the assembly
+            (0, 0, 0xE, 0x0) => self.clear_display(self.require(display, "display")?),
+            (0, 0, 0xE, 0xE) => self.ret()?,
+            (0, 0, 0xC, _) => self.scroll_down(d, self.require(display, "display")?),
+            (0, 0, 0xF, 0xB) => self.scroll_right(self.require(display, "display")?),
+            (0, 0, 0xF, 0xC) => self.scroll_left(self.require(display, "display")?),
+            (0, 0, 0xF, 0xD) => { return Ok(StepOutcome::Exit); }
+            (0, 0, 0xF, 0xE) => self.set_hires(false, self.require(display, "display")?),
+            (0, 0, 0xF, 0xF) => self.set_hires(true, self.require(display, "display")?),
             (0x1, _, _, _) => self.jump(nnn),
-            (0x2, _, _, _) => self.call(nnn),
-            (0x3, _, _, _) => self.skip_if_equal_x_to_kk(x, kk),
-            (0x4, _, _, _) => self.skip_if_not_equal_x_to_kk(x, kk),
-            (0x5, _, _, 0x0) => self.skip_if_equal_registers(x, y),
+            (0x2, _, _, _) => self.call(nnn)?,
+            (0x3, _, _, _) => self.skip_if_equal_x_to_kk(x, kk, ram),
+            (0x4, _, _, _) => self.skip_if_not_equal_x_to_kk(x, kk, ram),
+            (0x5, _, _, 0x0) => self.skip_if_equal_registers(x, y, ram),
+            (0x5, _, _, 0x2) => self.copy_x_to_y_to_ram(x, y, ram)?,
+            (0x5, _, _, 0x3) => self.copy_ram_to_x_to_y(x, y, ram)?,
             (0x6, _, _, _) => self.put_value_in_register(x, kk),
             (0x7, _, _, _) => self.add_kk_to_x(x, kk),
             (0x8, _, _, 0x0) => self.put_y_in_x(x, y),
@@ -77,72 +308,108 @@ impl CPU {
             (0x8, _, _, 0x3) => self.xor_y_in_x(x, y),
             (0x8, _, _, 0x4) => self.add_y_to_x(x, y),
             (0x8, _, _, 0x5) => self.sub_y_from_x(x, y),
-            (0x8, _, 0x0, 0x6) => self.rshift_x(x),
+            (0x8, _, _, 0x6) => self.rshift_x(x, y),
             (0x8, _, _, 0x7) => self.sub_x_from_y(x, y),
-            (0x8, _, 0x0, 0xE) => self.lshift_x(x),
-            (0x9, _, _, 0x0) => self.skip_if_not_equal_registers(x, y),
+            (0x8, _, _, 0xE) => self.lshift_x(x, y),
+            (0x9, _, _, 0x0) => self.skip_if_not_equal_registers(x, y, ram),
             (0xA, _, _, _) => self.set_i(nnn),
-            (0xB, _, _, _) => self.jump_plus_v0(nnn),
+            (0xB, _, _, _) => self.jump_plus_v0(x, nnn),
             (0xC, _, _, _) => self.random_and_value(x, kk),
-            (0xD, _, _, _) => self.draw(ram, x, y, d, display.unwrap()),
-            (0xE, _, 0x9, 0xE) => self.skip_if_key_pressed(x, keypad.unwrap()),
-            (0xE, _, 0xA, 0xE) => self.skip_if_key_not_pressed(x, keypad.unwrap()),
+            (0xD, _, _, 0x0) => self.draw_large(ram, x, y, self.require(display, "display")?)?,
+            (0xD, _, _, _) => self.draw(ram, x, y, d, self.require(display, "display")?)?,
+            (0xE, _, 0x9, 0xE) => self.skip_if_key_pressed(x, self.require(keypad, "keypad")?, ram)?,
+            (0xE, _, 0xA, 0xE) => self.skip_if_key_not_pressed(x, self.require(keypad, "keypad")?, ram)?,
+            (0xF, 0x0, 0x0, 0x0) => self.long_set_i(ram)?,
+            (0xF, _, 0x0, 0x1) => self.select_planes(x, self.require(display, "display")?),
+            (0xF, 0x0, 0x0, 0x2) => self.load_audio_pattern(ram)?,
             (0xF, _, 0x0, 0x7) => self.set_register_to_delay(x),
-            (0xF, _, 0x0, 0xA) => self.await_any_key_press(x, keypad.unwrap()),
+            (0xF, _, 0x0, 0xA) => self.await_key(x, self.require(keypad, "keypad")?, &previous_keys),
             (0xF, _, 0x1, 0x5) => self.set_delay_to_register(x),
             (0xF, _, 0x1, 0x8) => self.set_sound_to_register(x),
             (0xF, _, 0x1, 0xE) => self.add_register_to_i(x),
             (0xF, _, 0x2, 0x9) => self.set_i_to_char_loc(x),
-            (0xF, _, 0x3, 0x3) => self.register_to_bcd(x, ram),
-            (0xF, _, 0x5, 0x5) => self.copy_x_to_ram(x, ram),
-            (0xF, _, 0x6, 0x5) => self.copy_ram_to_x(x, ram),
-            _ => { panic!("Invalid instruction {:04X}", opcode); }
+            (0xF, _, 0x3, 0x0) => self.set_i_to_large_char_loc(x),
+            (0xF, _, 0x3, 0x3) => self.register_to_bcd(x, ram)?,
+            (0xF, _, 0x3, 0xA) => self.set_pitch_to_register(x),
+            (0xF, _, 0x5, 0x5) => self.copy_x_to_ram(x, ram)?,
+            (0xF, _, 0x6, 0x5) => self.copy_ram_to_x(x, ram)?,
+            (0xF, _, 0x7, 0x5) => self.copy_x_to_flags(x),
+            (0xF, _, 0x8, 0x5) => self.copy_flags_to_x(x),
+            _ => { return Err(CpuError::InvalidOpcode { pc: self.instruction_address(), opcode }); }
         }
-        true
+        Ok(StepOutcome::Continue)
     }
 
-    fn clear_display(&mut self, display: &mut Display) {
+    fn clear_display(&mut self, display: &mut dyn Display) {
         display.clear_display();
     }
 
-    fn ret(&mut self) {
+    fn scroll_down(&mut self, amount: u8, display: &mut dyn Display) {
+        display.v_ram().scroll_down(amount as usize);
+    }
+
+    fn scroll_right(&mut self, display: &mut dyn Display) {
+        display.v_ram().scroll_right(4);
+    }
+
+    fn scroll_left(&mut self, display: &mut dyn Display) {
+        display.v_ram().scroll_left(4);
+    }
+
+    fn set_hires(&mut self, hires: bool, display: &mut dyn Display) {
+        display.v_ram().set_hires(hires);
+    }
+
+    fn ret(&mut self) -> Result<(), CpuError> {
         // return from a system call
         if self.stack_pointer == 0 {
-            panic!("Stack underflow!");
+            return Err(CpuError::StackUnderflow { pc: self.instruction_address() });
         }
         self.stack_pointer -= 1;
         self.program_counter = self.stack[self.stack_pointer] as usize;
+        Ok(())
     }
 
     fn jump(&mut self, addr: u16) {
         self.program_counter = addr as usize;
     }
 
-    fn call(&mut self, addr: u16) {
+    fn call(&mut self, addr: u16) -> Result<(), CpuError> {
         // call a subroutine
         if self.stack_pointer >= self.stack.len() {
-            panic!("Stack overflow, max call stack is {}!", STACK_SIZE);
+            return Err(CpuError::StackOverflow { pc: self.instruction_address() });
         }
         self.stack[self.stack_pointer] = self.program_counter as u16;
         self.stack_pointer += 1;
         self.program_counter = addr as usize;
+        Ok(())
     }
 
-    fn skip_if_equal_x_to_kk(&mut self, register: u8, value: u8) {
-        if self.registers[register as usize] == value {
+    // skip the next instruction, which takes 4 bytes for the XO-CHIP long load of I
+    fn skip_next_instruction(&mut self, ram: &RAM) {
+        let next = self.program_counter;
+        if next + 1 < ram.size() && ram.get_u16(next) == LONG_LOAD_OPCODE {
+            self.program_counter += 4;
+        } else {
             self.program_counter += 2;
         }
     }
 
-    fn skip_if_not_equal_x_to_kk(&mut self, register: u8, value: u8) {
+    fn skip_if_equal_x_to_kk(&mut self, register: u8, value: u8, ram: &RAM) {
+        if self.registers[register as usize] == value {
+            self.skip_next_instruction(ram);
+        }
+    }
+
+    fn skip_if_not_equal_x_to_kk(&mut self, register: u8, value: u8, ram: &RAM) {
         if self.registers[register as usize] != value {
-            self.program_counter += 2;
+            self.skip_next_instruction(ram);
         }
     }
 
-    fn skip_if_equal_registers(&mut self, register1: u8, register2: u8) {
+    fn skip_if_equal_registers(&mut self, register1: u8, register2: u8, ram: &RAM) {
         if self.registers[register1 as usize] == self.registers[register2 as usize] {
-            self.program_counter += 2;
+            self.skip_next_instruction(ram);
         }
     }
 
@@ -167,14 +434,23 @@ impl CPU {
 
     fn or_y_in_x(&mut self, register1: u8, register2: u8) {
         self.registers[register1 as usize] |= self.registers[register2 as usize];
+        self.reset_flag_after_logic();
     }
 
     fn and_y_in_x(&mut self, register1: u8, register2: u8) {
         self.registers[register1 as usize] &= self.registers[register2 as usize];
+        self.reset_flag_after_logic();
     }
 
     fn xor_y_in_x(&mut self, register1: u8, register2: u8) {
         self.registers[register1 as usize] ^= self.registers[register2 as usize];
+        self.reset_flag_after_logic();
+    }
+
+    fn reset_flag_after_logic(&mut self) {
+        if self.quirks.logic_resets_vf {
+            self.registers[SPECIAL_REGISTER] = 0;
+        }
     }
 
     fn add_y_to_x(&mut self, register1: u8, register2: u8) {
@@ -193,9 +469,19 @@ impl CPU {
         }
     }
 
-    fn rshift_x(&mut self, register1: u8) {
-        self.registers[SPECIAL_REGISTER] = self.registers[register1 as usize] & 1;
-        self.registers[register1 as usize] >>= 1;
+    fn rshift_x(&mut self, register1: u8, register2: u8) {
+        let value = self.shift_source(register1, register2);
+        self.registers[register1 as usize] = value >> 1;
+        self.registers[SPECIAL_REGISTER] = value & 1;
+    }
+
+    // the register that is shifted by 8xy6 and 8xyE
+    fn shift_source(&self, register1: u8, register2: u8) -> u8 {
+        if self.quirks.shift_uses_vy {
+            self.registers[register2 as usize]
+        } else {
+            self.registers[register1 as usize]
+        }
     }
 
     fn sub_x_from_y(&mut self, register1: u8, register2: u8) {
@@ -210,14 +496,15 @@ impl CPU {
         }
     }
 
-    fn lshift_x(&mut self, register1: u8) {
-        self.registers[SPECIAL_REGISTER] = self.registers[register1 as usize] >> 7 & 1;
-        self.registers[register1 as usize] <<= 1;
+    fn lshift_x(&mut self, register1: u8, register2: u8) {
+        let value = self.shift_source(register1, register2);
+        self.registers[register1 as usize] = value << 1;
+        self.registers[SPECIAL_REGISTER] = value >> 7 & 1;
     }
 
-    fn skip_if_not_equal_registers(&mut self, register1: u8, register2: u8) {
+    fn skip_if_not_equal_registers(&mut self, register1: u8, register2: u8, ram: &RAM) {
         if self.registers[register1 as usize] != self.registers[register2 as usize] {
-            self.program_counter += 2;
+            self.skip_next_instruction(ram);
         }
     }
 
@@ -226,48 +513,86 @@ impl CPU {
         self.i = nnn;
     }
 
-    fn jump_plus_v0(&mut self, addr: u16) {
-        self.program_counter = addr as usize + self.registers[0x0] as usize;
+    fn jump_plus_v0(&mut self, register: u8, addr: u16) {
+        let offset_register = if self.quirks.jump_uses_vx { register as usize } else { 0x0 };
+        self.program_counter = addr as usize + self.registers[offset_register] as usize;
     }
 
     fn random_and_value(&mut self, register: u8, value: u8) {
-        self.set_register(register as usize, rand::thread_rng().gen_range(0..=255) & value);
+        let random: u8 = self.rng.gen_range(0..=255);
+        self.set_register(register as usize, random & value);
     }
 
-    fn draw(&mut self, ram: &mut RAM, register1: u8, register2: u8, nr: u8, display: &mut Display) {
+    fn draw(&mut self, ram: &mut RAM, register1: u8, register2: u8, nr: u8, display: &mut dyn Display) -> Result<(), CpuError> {
+        // every selected XO-CHIP plane has its own sprite, stored one after the other
+        let length = nr as usize * display.v_ram().plane_count();
+        self.check_memory(ram, self.i as usize, length)?;
         let x = self.read_register(register1 as usize);
         let y = self.read_register(register2 as usize);
-        for increase in 0..nr {
-            let value = ram.get(self.i as usize + increase as usize);
-            display.draw(x, y + increase, value);
-        }
+        let rows: Vec<u8> = (0..length).map(|row| ram.get(self.i as usize + row)).collect();
+        let collision = display.v_ram().draw_sprite(x, y, &rows, self.quirks.clip_sprites);
+        self.registers[SPECIAL_REGISTER] = collision as u8;
+        Ok(())
+    }
+
+    fn draw_large(&mut self, ram: &mut RAM, register1: u8, register2: u8, display: &mut dyn Display) -> Result<(), CpuError> {
+        // a 16x16 sprite of two bytes per row for every selected plane
+        let length = 16 * display.v_ram().plane_count();
+        self.check_memory(ram, self.i as usize, length * 2)?;
+        let x = self.read_register(register1 as usize);
+        let y = self.read_register(register2 as usize);
+        let rows: Vec<u16> = (0..length).map(|row| ram.get_u16(self.i as usize + row * 2)).collect();
+        let collision = display.v_ram().draw_large_sprite(x, y, &rows, self.quirks.clip_sprites);
+        self.registers[SPECIAL_REGISTER] = collision as u8;
+        Ok(())
     }
 
-    fn skip_if_key_pressed(&mut self, register: u8, keypad: &[bool; 16]) {
+    fn skip_if_key_pressed(&mut self, register: u8, keypad: &[bool; 16], ram: &RAM) -> Result<(), CpuError> {
         let value = self.read_register(register as usize);
         match keypad.get(value as usize){
             Some(pressed) => {
                 if *pressed{
-                    self.program_counter += 2;
+                    self.skip_next_instruction(ram);
                 }
+                Ok(())
             },
-            None => panic!("Invalid key supplied; {}", value)
+            None => Err(CpuError::InvalidKey { pc: self.instruction_address(), key: value })
         }
     }
 
-    fn skip_if_key_not_pressed(&mut self, register: u8, keypad: &[bool; 16]) {
+    fn skip_if_key_not_pressed(&mut self, register: u8, keypad: &[bool; 16], ram: &RAM) -> Result<(), CpuError> {
         let value = self.read_register(register as usize);
         match keypad.get(value as usize){
             Some(pressed) => {
                 if !*pressed{
-                    self.program_counter += 2;
+                    self.skip_next_instruction(ram);
                 }
+                Ok(())
             },
-            None => panic!("Invalid key supplied; {}", value)
-        }    }
+            None => Err(CpuError::InvalidKey { pc: self.instruction_address(), key: value })
+        }
+    }
 
-    fn set_register_to_delay(&mut self, _register: u8) {
-        todo!("Register to delay is missing implementation");
+    fn set_register_to_delay(&mut self, register: u8) {
+        self.set_register(register as usize, self.delay_timer());
+    }
+
+    // repeats itself until a key is pressed and released again
+    fn await_key(&mut self, register: u8, keypad: &[bool; 16], previous_keys: &[bool; 16]) {
+        if self.quirks.key_wait_on_press {
+            self.await_any_key_press(register, keypad);
+            return;
+        }
+        match self.awaited_key {
+            Some(key) if !keypad[key as usize] => {
+                self.set_register(register as usize, key);
+                self.awaited_key = None;
+                return;
+            }
+            Some(_) => (),
+            None => self.awaited_key = (0..16).find(|key| keypad[*key as usize] && !previous_keys[*key as usize])
+        }
+        self.program_counter -= 2;
     }
 
     fn await_any_key_press(&mut self, register: u8, keypad: &[bool; 16]) {
@@ -280,46 +605,138 @@ impl CPU {
         self.program_counter -= 2;
     }
 
-    fn set_delay_to_register(&mut self, _register: u8) {
-        todo!("Delay to register is missing implementation");
+    fn set_delay_to_register(&mut self, register: u8) {
+        self.delay_timer = self.read_register(register as usize);
     }
 
-    fn set_sound_to_register(&mut self, _register: u8) {
-        todo!("Sound to register is missing implementation");
+    fn set_sound_to_register(&mut self, register: u8) {
+        self.sound_timer = self.read_register(register as usize);
     }
 
     fn add_register_to_i(&mut self, register: u8) {
-        self.i += self.registers[register as usize] as u16;
+        self.i = self.i.wrapping_add(self.registers[register as usize] as u16);
     }
 
     fn set_i_to_char_loc(&mut self, register: u8) {
         self.i = (LETTER_SIZE * self.read_register(register as usize) as usize) as u16;
     }
 
-    fn register_to_bcd(&mut self, register: u8, ram: &mut RAM) {
+    fn set_i_to_large_char_loc(&mut self, register: u8) {
+        let letter = self.read_register(register as usize) as usize & 0xF;
+        self.i = (LARGE_LETTERS_OFFSET + LARGE_LETTER_SIZE * letter) as u16;
+    }
+
+    fn register_to_bcd(&mut self, register: u8, ram: &mut RAM) -> Result<(), CpuError> {
+        self.check_memory(ram, self.i as usize, 3)?;
         ram.set(self.i as usize, self.registers[register as usize] / 100);
         ram.set(self.i as usize + 1, (self.registers[register as usize] % 100) / 10);
         ram.set(self.i as usize + 2, self.registers[register as usize] % 10);
+        Ok(())
     }
 
-    fn copy_x_to_ram(&mut self, x: u8, ram: &mut RAM) {
+    fn copy_x_to_ram(&mut self, x: u8, ram: &mut RAM) -> Result<(), CpuError> {
         // copies the values of registers V0 through Vx into memory, starting at the address in i
+        self.check_memory(ram, self.i as usize, x as usize + 1)?;
         for nr in 0..x as usize + 1 {
             ram.set(self.i as usize + nr, self.registers[nr]);
         }
+        self.increment_i_after_load_store(x);
+        Ok(())
     }
 
-    fn copy_ram_to_x(&mut self, x: u8, ram: &mut RAM) {
+    fn copy_ram_to_x(&mut self, x: u8, ram: &mut RAM) -> Result<(), CpuError> {
+        self.check_memory(ram, self.i as usize, x as usize + 1)?;
         for nr in 0..x as usize + 1 {
             self.registers[nr] = ram.get((self.i as usize) + nr);
         }
+        self.increment_i_after_load_store(x);
+        Ok(())
+    }
+
+    fn copy_x_to_y_to_ram(&mut self, x: u8, y: u8, ram: &mut RAM) -> Result<(), CpuError> {
+        // XO-CHIP save of a register range, in reverse order when x is bigger than y. I is not changed
+        let registers = register_range(x, y);
+        self.check_memory(ram, self.i as usize, registers.len())?;
+        for (offset, register) in registers.into_iter().enumerate() {
+            ram.set(self.i as usize + offset, self.registers[register]);
+        }
+        Ok(())
+    }
+
+    fn copy_ram_to_x_to_y(&mut self, x: u8, y: u8, ram: &mut RAM) -> Result<(), CpuError> {
+        let registers = register_range(x, y);
+        self.check_memory(ram, self.i as usize, registers.len())?;
+        for (offset, register) in registers.into_iter().enumerate() {
+            self.registers[register] = ram.get(self.i as usize + offset);
+        }
+        Ok(())
+    }
+
+    fn long_set_i(&mut self, ram: &RAM) -> Result<(), CpuError> {
+        // the address is stored in the word following the instruction
+        self.check_memory(ram, self.program_counter, 2)?;
+        self.i = ram.get_u16(self.program_counter);
+        self.program_counter += 2;
+        Ok(())
+    }
+
+    fn select_planes(&mut self, planes: u8, display: &mut dyn Display) {
+        display.v_ram().set_planes(planes);
+    }
+
+    fn load_audio_pattern(&mut self, ram: &RAM) -> Result<(), CpuError> {
+        self.check_memory(ram, self.i as usize, 16)?;
+        let mut pattern = [0; 16];
+        for (offset, value) in pattern.iter_mut().enumerate() {
+            *value = ram.get(self.i as usize + offset);
+        }
+        self.audio_pattern = Some(pattern);
+        Ok(())
+    }
+
+    fn set_pitch_to_register(&mut self, register: u8) {
+        self.pitch = self.read_register(register as usize);
+    }
+
+    fn copy_x_to_flags(&mut self, x: u8) {
+        self.rpl_flags[..x as usize + 1].copy_from_slice(&self.registers[..x as usize + 1]);
+    }
+
+    fn copy_flags_to_x(&mut self, x: u8) {
+        self.registers[..x as usize + 1].copy_from_slice(&self.rpl_flags[..x as usize + 1]);
+    }
+
+    fn increment_i_after_load_store(&mut self, x: u8) {
+        if self.quirks.load_store_increments_i {
+            self.i = self.i.wrapping_add(x as u16 + 1);
+        }
+    }
+}
+
+// registers x through y, counting down when x is bigger than y
+fn register_range(x: u8, y: u8) -> Vec<usize> {
+    if x <= y {
+        (x as usize..=y as usize).collect()
+    } else {
+        (y as usize..=x as usize).rev().collect()
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::cpu::{CPU, SPECIAL_REGISTER};
-    use crate::ram::{LETTER_SIZE, RAM, RAM_OFFSET};
+    use crate::cpu::{CpuError, StepOutcome, CPU, SPECIAL_REGISTER};
+    use crate::drivers::{Cartridge, Display, HeadlessDisplay};
+    use crate::quirks::Quirks;
+    use crate::ram::{LARGE_LETTERS_OFFSET, LARGE_LETTER_SIZE, LETTER_SIZE, RAM, RAM_OFFSET, XO_RAM_SIZE};
+
+    #[test]
+    fn test_binary_rom_clear_display() {
+        // binary ROMs start with 00E0, which clears the display instead of being an invalid instruction
+        let mut cpu = CPU::new();
+        let mut ram = RAM::new();
+        Cartridge::load_binary(&[0x00, 0xE0, 0x00, 0x00], &mut ram).unwrap();
+        assert_eq!(cpu.tick(&mut ram, None, None), Err(CpuError::DeviceMissing { pc: RAM_OFFSET, device: "display" }));
+    }
 
     #[test]
     fn test_set_i() {
@@ -327,7 +744,7 @@ mod tests {
         let mut ram = RAM::new();
         ram.set_u16(RAM_OFFSET, 0xA001);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -341,7 +758,7 @@ mod tests {
         ram.set_u16(RAM_OFFSET, 0xF029);
         cpu.set_register(0, 0);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -355,7 +772,7 @@ mod tests {
         ram.set_u16(RAM_OFFSET, 0xFC29);
         cpu.set_register(0xCusize, 0xCu8);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -369,7 +786,7 @@ mod tests {
         ram.set_u16(RAM_OFFSET, 0xF033);
         cpu.set_register(0, 129);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -386,7 +803,7 @@ mod tests {
         cpu.set_register(0, 5);
         cpu.i = 2;
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -400,13 +817,32 @@ mod tests {
         let mut ram = RAM::new();
         ram.set_u16(RAM_OFFSET, 0xC000);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
         assert_eq!(cpu.read_register(0), 0);
     }
 
+    #[test]
+    fn test_random_seed() {
+        let mut ram = RAM::new();
+        for address in (RAM_OFFSET..RAM_OFFSET + 32).step_by(2) {
+            ram.set_u16(address, 0xC0FF + (((address - RAM_OFFSET) as u16 / 2) << 8));
+        }
+        let mut first = CPU::new();
+        let mut second = CPU::new();
+        first.set_seed(1234);
+        second.set_seed(first.seed());
+        for _ in 0..16 {
+            first.tick(&mut ram, None, None).unwrap();
+            second.tick(&mut ram, None, None).unwrap();
+        }
+        for register in 0..16 {
+            assert_eq!(first.read_register(register), second.read_register(register));
+        }
+    }
+
     #[test]
     fn test_rshift_x_sig_1() {
         let mut cpu = CPU::new();
@@ -414,7 +850,7 @@ mod tests {
         ram.set_u16(RAM_OFFSET, 0x8006);
         cpu.set_register(0, 0b101);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -429,7 +865,7 @@ mod tests {
         ram.set_u16(RAM_OFFSET, 0x8006);
         cpu.set_register(0, 0b110);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -444,7 +880,7 @@ mod tests {
         ram.set_u16(RAM_OFFSET, 0x800E);
         cpu.set_register(0, 0b10001010);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -459,7 +895,7 @@ mod tests {
         ram.set_u16(RAM_OFFSET, 0x800E);
         cpu.set_register(0, 0b01001010);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -475,7 +911,7 @@ mod tests {
         cpu.set_register(0, 0b001u8);
         cpu.set_register(1, 0b101u8);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -490,7 +926,7 @@ mod tests {
         cpu.set_register(0, 0b001u8);
         cpu.set_register(1, 0b101u8);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -505,7 +941,7 @@ mod tests {
         cpu.set_register(0, 5);
         cpu.set_register(1, 3);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -521,7 +957,7 @@ mod tests {
         cpu.set_register(0, 5);
         cpu.set_register(1, 6);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -537,7 +973,7 @@ mod tests {
         cpu.set_register(0, 3);
         cpu.set_register(1, 5);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -553,7 +989,7 @@ mod tests {
         cpu.set_register(0, 6);
         cpu.set_register(1, 5);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -569,7 +1005,7 @@ mod tests {
         cpu.set_register(0, 0b011u8);
         cpu.set_register(1, 0b101u8);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -584,7 +1020,7 @@ mod tests {
         cpu.set_register(1, 5);
         ram.set_u16(RAM_OFFSET, 0x8010);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -599,7 +1035,7 @@ mod tests {
         cpu.set_register(0, 1);
         ram.set_u16(RAM_OFFSET, 0x7001);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -612,7 +1048,7 @@ mod tests {
         let mut ram = RAM::new();
         ram.set_u16(RAM_OFFSET, 0x6009);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -628,7 +1064,7 @@ mod tests {
         ram.set_u16(RAM_OFFSET, 0x1204);
         ram.set_u16(RAM_OFFSET + 2, 0x8014);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -647,7 +1083,7 @@ mod tests {
         ram.set_u16(RAM_OFFSET, 0xB204);
         ram.set_u16(RAM_OFFSET + 2, 0x8014);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -667,7 +1103,7 @@ mod tests {
         ram.set_u16(RAM_OFFSET, 0x3102);
         ram.set_u16(RAM_OFFSET + 2, 0x8014);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -684,7 +1120,7 @@ mod tests {
         ram.set_u16(RAM_OFFSET, 0x3103);
         ram.set_u16(RAM_OFFSET + 2, 0x8014);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -701,7 +1137,7 @@ mod tests {
         ram.set_u16(RAM_OFFSET, 0x4102);
         ram.set_u16(RAM_OFFSET + 2, 0x8014);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -718,7 +1154,7 @@ mod tests {
         ram.set_u16(RAM_OFFSET, 0x4002);
         ram.set_u16(RAM_OFFSET + 2, 0x8014);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -735,7 +1171,7 @@ mod tests {
         ram.set_u16(RAM_OFFSET, 0x5010);
         ram.set_u16(RAM_OFFSET + 2, 0x8014);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -752,7 +1188,7 @@ mod tests {
         ram.set_u16(RAM_OFFSET, 0x5010);
         ram.set_u16(RAM_OFFSET + 2, 0x8014);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -769,7 +1205,7 @@ mod tests {
         ram.set_u16(RAM_OFFSET, 0x9010);
         ram.set_u16(RAM_OFFSET + 2, 0x8014);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -786,7 +1222,7 @@ mod tests {
         ram.set_u16(RAM_OFFSET, 0x9010);
         ram.set_u16(RAM_OFFSET + 2, 0x8014);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -808,7 +1244,7 @@ mod tests {
         ram.sets(0x226, &add_call);
 
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -817,7 +1253,6 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "Stack overflow, max call stack is 16!")]
     fn test_stack_overflow() {
         // test both call and ret
         let mut cpu = CPU::new();
@@ -832,11 +1267,66 @@ mod tests {
         ram.set_u16(RAM_OFFSET, 0x2226);
         ram.sets(0x226, &add_call);
 
-        loop {
-            if !cpu.tick(&mut ram, None, None) {
-                break;
+        let error = loop {
+            if let Err(error) = cpu.tick(&mut ram, None, None) {
+                break error;
             }
-        }
+        };
+        assert_eq!(error, CpuError::StackOverflow { pc: 0x228 });
+        assert_eq!(error.to_string(), "Stack overflow, max call stack is 16 at 0x228");
+    }
+
+    #[test]
+    fn test_stack_underflow() {
+        let mut cpu = CPU::new();
+        let mut ram = RAM::new();
+        ram.set_u16(RAM_OFFSET, 0x00EE);
+        assert_eq!(cpu.tick(&mut ram, None, None), Err(CpuError::StackUnderflow { pc: RAM_OFFSET }));
+    }
+
+    #[test]
+    fn test_invalid_opcode() {
+        let mut cpu = CPU::new();
+        let mut ram = RAM::new();
+        ram.set_u16(RAM_OFFSET, 0xFFFF);
+        assert_eq!(cpu.tick(&mut ram, None, None), Err(CpuError::InvalidOpcode { pc: RAM_OFFSET, opcode: 0xFFFF }));
+    }
+
+    #[test]
+    fn test_device_missing() {
+        let mut cpu = CPU::new();
+        let mut ram = RAM::new();
+        ram.set_u16(RAM_OFFSET, 0xE09E);
+        assert_eq!(cpu.tick(&mut ram, None, None), Err(CpuError::DeviceMissing { pc: RAM_OFFSET, device: "keypad" }));
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        let mut cpu = CPU::new();
+        let mut ram = RAM::new();
+        ram.set_u16(RAM_OFFSET, 0xE09E);
+        cpu.set_register(0, 0x10);
+        let keypad = [false; 16];
+        assert_eq!(cpu.tick(&mut ram, Some(&keypad), None), Err(CpuError::InvalidKey { pc: RAM_OFFSET, key: 0x10 }));
+    }
+
+    #[test]
+    fn test_memory_out_of_bounds() {
+        let mut cpu = CPU::new();
+        let mut ram = RAM::new();
+        ram.set_u16(RAM_OFFSET, 0xAFFF);
+        ram.set_u16(RAM_OFFSET + 2, 0xF155);
+        assert_eq!(cpu.tick(&mut ram, None, None), Ok(StepOutcome::Continue));
+        assert_eq!(cpu.tick(&mut ram, None, None), Err(CpuError::MemoryOutOfBounds { pc: RAM_OFFSET + 2, address: 0x1000 }));
+    }
+
+    #[test]
+    fn test_program_counter_out_of_bounds() {
+        let mut cpu = CPU::new();
+        let mut ram = RAM::new();
+        ram.set_u16(RAM_OFFSET, 0x1FFF);
+        assert_eq!(cpu.tick(&mut ram, None, None), Ok(StepOutcome::Continue));
+        assert_eq!(cpu.tick(&mut ram, None, None), Err(CpuError::MemoryOutOfBounds { pc: 0xFFF, address: 0x1000 }));
     }
 
     #[test]
@@ -847,7 +1337,7 @@ mod tests {
         cpu.set_register(1, 2);
         ram.set_u16(RAM_OFFSET, 0x8014);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -864,7 +1354,7 @@ mod tests {
         cpu.set_register(1, 255);
         ram.set_u16(RAM_OFFSET, 0x8014);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -885,7 +1375,7 @@ mod tests {
         ram.set_u16(RAM_OFFSET, 0xA010);
         ram.set_u16(RAM_OFFSET + 2, 0xF455);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
@@ -906,12 +1396,332 @@ mod tests {
         ram.set(RAM_OFFSET + 0x10, 1);
         ram.set(RAM_OFFSET + 0x11, 52);
         loop {
-            if !cpu.tick(&mut ram, None, None) {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
                 break;
             }
         }
         assert_eq!(cpu.read_register(0), 1);
         assert_eq!(cpu.read_register(1), 52);
     }
-}
 
+    #[test]
+    fn test_set_delay_to_register() {
+        let mut cpu = CPU::new();
+        let mut ram = RAM::new();
+        ram.set_u16(RAM_OFFSET, 0xF315);
+        cpu.set_register(3, 42);
+        loop {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
+                break;
+            }
+        }
+        assert_eq!(cpu.delay_timer, 42);
+    }
+
+    #[test]
+    fn test_set_register_to_delay() {
+        let mut cpu = CPU::new();
+        let mut ram = RAM::new();
+        ram.set_u16(RAM_OFFSET, 0xF107);
+        cpu.delay_timer = 12;
+        loop {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
+                break;
+            }
+        }
+        assert_eq!(cpu.read_register(1), 12);
+    }
+
+    #[test]
+    fn test_set_sound_to_register() {
+        let mut cpu = CPU::new();
+        let mut ram = RAM::new();
+        ram.set_u16(RAM_OFFSET, 0xF218);
+        cpu.set_register(2, 7);
+        loop {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
+                break;
+            }
+        }
+        assert_eq!(cpu.sound_timer, 7);
+        assert_eq!(cpu.delay_timer, 0);
+    }
+
+    #[test]
+    fn test_decrement_timers() {
+        let mut cpu = CPU::new();
+        cpu.delay_timer = 10;
+        cpu.sound_timer = 2;
+        for _ in 0..3 {
+            cpu.decrement_timers();
+        }
+        assert_eq!(cpu.delay_timer, 7);
+        // timers stop at 0
+        assert_eq!(cpu.sound_timer, 0);
+    }
+
+    #[test]
+    fn test_quirk_shift_uses_vy() {
+        let mut cpu = CPU::new();
+        let mut ram = RAM::new();
+        cpu.set_quirks(Quirks::cosmac_vip());
+        ram.set_u16(RAM_OFFSET, 0x8016);
+        ram.set_u16(RAM_OFFSET + 2, 0x823E);
+        cpu.set_register(0, 0b1000);
+        cpu.set_register(1, 0b101);
+        cpu.set_register(3, 0b10000001);
+        loop {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
+                break;
+            }
+        }
+        assert_eq!(cpu.read_register(0), 0b10);
+        assert_eq!(cpu.read_register(2), 0b10);
+        assert_eq!(cpu.read_register(SPECIAL_REGISTER), 1);
+    }
+
+    #[test]
+    fn test_quirk_load_store_increments_i() {
+        let mut cpu = CPU::new();
+        let mut ram = RAM::new();
+        cpu.set_quirks(Quirks::cosmac_vip());
+        ram.set_u16(RAM_OFFSET, 0xA300);
+        ram.set_u16(RAM_OFFSET + 2, 0xF255);
+        ram.set_u16(RAM_OFFSET + 4, 0xF165);
+        loop {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
+                break;
+            }
+        }
+        assert_eq!(cpu.i, 0x305);
+    }
+
+    #[test]
+    fn test_quirk_jump_uses_vx() {
+        let mut cpu = CPU::new();
+        let mut ram = RAM::new();
+        cpu.set_quirks(Quirks::superchip());
+        cpu.set_register(0, 8);
+        cpu.set_register(2, 2);
+        ram.set_u16(RAM_OFFSET, 0xB204);
+        ram.set_u16(RAM_OFFSET + 2, 0x8014);
+        loop {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
+                break;
+            }
+        }
+        assert_eq!(cpu.program_counter, 520);
+    }
+
+    #[test]
+    fn test_await_key_release() {
+        let mut cpu = CPU::new();
+        let mut ram = RAM::new();
+        ram.set_u16(RAM_OFFSET, 0x6000);
+        ram.set_u16(RAM_OFFSET + 2, 0xF30A);
+        let mut keypad = [false; 16];
+        // a key that is already held before Fx0A doesn't count
+        keypad[0x2] = true;
+        for _ in 0..4 {
+            cpu.tick(&mut ram, Some(&keypad), None).unwrap();
+        }
+        // pressing another key isn't enough
+        keypad[0x7] = true;
+        for _ in 0..3 {
+            cpu.tick(&mut ram, Some(&keypad), None).unwrap();
+        }
+        // releasing the old key does nothing
+        keypad[0x2] = false;
+        cpu.tick(&mut ram, Some(&keypad), None).unwrap();
+        assert_eq!(cpu.read_register(3), 0);
+        keypad[0x7] = false;
+        cpu.tick(&mut ram, Some(&keypad), None).unwrap();
+        assert_eq!(cpu.read_register(3), 0x7);
+        assert_eq!(cpu.tick(&mut ram, Some(&keypad), None), Ok(StepOutcome::Exit));
+    }
+
+    #[test]
+    fn test_quirk_key_wait_on_press() {
+        let mut cpu = CPU::new();
+        let mut ram = RAM::new();
+        cpu.set_quirks(Quirks { key_wait_on_press: true, ..Quirks::default() });
+        ram.set_u16(RAM_OFFSET, 0xF30A);
+        let mut keypad = [false; 16];
+        keypad[0x2] = true;
+        cpu.tick(&mut ram, Some(&keypad), None).unwrap();
+        assert_eq!(cpu.read_register(3), 0x2);
+        assert_eq!(cpu.tick(&mut ram, Some(&keypad), None), Ok(StepOutcome::Exit));
+    }
+
+    #[test]
+    fn test_quirk_logic_resets_vf() {
+        let mut cpu = CPU::new();
+        let mut ram = RAM::new();
+        cpu.set_quirks(Quirks::cosmac_vip());
+        cpu.set_register(SPECIAL_REGISTER, 1);
+        ram.set_u16(RAM_OFFSET, 0x8011);
+        loop {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
+                break;
+            }
+        }
+        assert_eq!(cpu.read_register(SPECIAL_REGISTER), 0);
+    }
+
+    #[test]
+    fn test_superchip_exit() {
+        let mut cpu = CPU::new();
+        let mut ram = RAM::new();
+        ram.set_u16(RAM_OFFSET, 0x00FD);
+        assert_eq!(cpu.tick(&mut ram, None, None), Ok(StepOutcome::Exit));
+    }
+
+    #[test]
+    fn test_set_i_to_large_char_loc() {
+        let mut cpu = CPU::new();
+        let mut ram = RAM::new();
+        ram.set_u16(RAM_OFFSET, 0xF330);
+        cpu.set_register(3, 7);
+        loop {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
+                break;
+            }
+        }
+        assert_eq!(cpu.i as usize, LARGE_LETTERS_OFFSET + 7 * LARGE_LETTER_SIZE);
+        assert_eq!(ram.get(cpu.i as usize + 4), 0x06);
+    }
+
+    #[test]
+    fn test_rpl_flags() {
+        let mut cpu = CPU::new();
+        let mut ram = RAM::new();
+        cpu.set_register(0, 4);
+        cpu.set_register(1, 5);
+        cpu.set_register(2, 6);
+        ram.set_u16(RAM_OFFSET, 0xF275);
+        ram.set_u16(RAM_OFFSET + 2, 0x6000);
+        ram.set_u16(RAM_OFFSET + 4, 0x6100);
+        ram.set_u16(RAM_OFFSET + 6, 0x6200);
+        ram.set_u16(RAM_OFFSET + 8, 0xF185);
+        loop {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
+                break;
+            }
+        }
+        assert_eq!(cpu.read_register(0), 4);
+        assert_eq!(cpu.read_register(1), 5);
+        assert_eq!(cpu.read_register(2), 0);
+    }
+
+    #[test]
+    fn test_long_set_i() {
+        let mut cpu = CPU::new();
+        let mut ram = RAM::with_size(XO_RAM_SIZE);
+        ram.set_u16(RAM_OFFSET, 0xF000);
+        ram.set_u16(RAM_OFFSET + 2, 0xE123);
+        ram.set_u16(RAM_OFFSET + 4, 0xF165);
+        ram.set(0xE124, 9);
+        loop {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
+                break;
+            }
+        }
+        assert_eq!(cpu.i, 0xE123);
+        assert_eq!(cpu.read_register(1), 9);
+    }
+
+    #[test]
+    fn test_skip_over_long_set_i() {
+        let mut cpu = CPU::new();
+        let mut ram = RAM::with_size(XO_RAM_SIZE);
+        cpu.set_register(0, 1);
+        ram.set_u16(RAM_OFFSET, 0x3001);
+        ram.set_u16(RAM_OFFSET + 2, 0xF000);
+        ram.set_u16(RAM_OFFSET + 4, 0xE123);
+        ram.set_u16(RAM_OFFSET + 6, 0x7001);
+        loop {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
+                break;
+            }
+        }
+        assert_eq!(cpu.i, 0);
+        assert_eq!(cpu.read_register(0), 2);
+    }
+
+    #[test]
+    fn test_copy_register_range() {
+        let mut cpu = CPU::new();
+        let mut ram = RAM::new();
+        cpu.set_register(2, 1);
+        cpu.set_register(3, 2);
+        cpu.set_register(4, 3);
+        ram.set_u16(RAM_OFFSET, 0xA300);
+        ram.set_u16(RAM_OFFSET + 2, 0x5242);
+        ram.set_u16(RAM_OFFSET + 4, 0x5753);
+        loop {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
+                break;
+            }
+        }
+        assert_eq!(ram.get(0x300), 1);
+        assert_eq!(ram.get(0x302), 3);
+        // loaded in reverse order
+        assert_eq!(cpu.read_register(7), 1);
+        assert_eq!(cpu.read_register(6), 2);
+        assert_eq!(cpu.read_register(5), 3);
+        assert_eq!(cpu.i, 0x300);
+    }
+
+    #[test]
+    fn test_audio_pattern() {
+        let mut cpu = CPU::new();
+        let mut ram = RAM::new();
+        cpu.set_register(0, 100);
+        ram.set_u16(RAM_OFFSET, 0xA300);
+        ram.set_u16(RAM_OFFSET + 2, 0xF002);
+        ram.set_u16(RAM_OFFSET + 4, 0xF03A);
+        ram.set(0x300, 0xF0);
+        ram.set(0x30F, 0x0F);
+        assert_eq!(cpu.audio_pattern(), None);
+        loop {
+            if cpu.tick(&mut ram, None, None).unwrap() == StepOutcome::Exit {
+                break;
+            }
+        }
+        let pattern = cpu.audio_pattern().unwrap();
+        assert_eq!(pattern[0], 0xF0);
+        assert_eq!(pattern[15], 0x0F);
+        assert_eq!(cpu.pitch(), 100);
+    }
+
+    #[test]
+    fn test_draw_headless() {
+        let mut cpu = CPU::new();
+        let mut ram = RAM::new();
+        let mut display = HeadlessDisplay::new();
+        // draw the 0 from the font at (2, 3) twice, the second time erases it and sets VF
+        ram.set_u16(RAM_OFFSET, 0x6002);
+        ram.set_u16(RAM_OFFSET + 2, 0x6103);
+        ram.set_u16(RAM_OFFSET + 4, 0xA000);
+        ram.set_u16(RAM_OFFSET + 6, 0xD015);
+        for _ in 0..4 {
+            cpu.tick(&mut ram, None, Some(&mut display)).unwrap();
+        }
+        let bits = display.bits();
+        assert_eq!(cpu.read_register(SPECIAL_REGISTER), 0);
+        assert!(bits[3 * 64 + 2]);
+        assert!(!bits[4 * 64 + 3]);
+        assert_eq!(bits.iter().filter(|bit| **bit).count(), 14);
+
+        ram.set_u16(RAM_OFFSET + 8, 0xD015);
+        cpu.tick(&mut ram, None, Some(&mut display)).unwrap();
+        assert_eq!(cpu.read_register(SPECIAL_REGISTER), 1);
+        assert!(display.bits().iter().all(|bit| !bit));
+
+        // clear the screen
+        display.v_ram().draw_sprite(0, 0, &[0xFF], false);
+        ram.set_u16(RAM_OFFSET + 10, 0x00E0);
+        cpu.tick(&mut ram, None, Some(&mut display)).unwrap();
+        assert!(display.bits().iter().all(|bit| !bit));
+    }
+}
@@ -1,30 +1,83 @@
+use std::fs;
 use std::fs::File;
-use std::io;
-use std::io::{BufRead, Write};
+use std::io::Write;
 use std::path::Path;
 use crate::ram::{RAM, RAM_OFFSET, RAM_SIZE};
 use crate::interpreter::{Interpreter};
 
+// extensions commonly used for raw CHIP-8 programs
+const BINARY_EXTENSIONS: [&str; 6] = ["ch8", "c8", "sc8", "xo8", "bin", "rom"];
+// extensions used for programs in the assembly language of this project
+const ASSEMBLY_EXTENSIONS: [&str; 2] = ["txt", "asm"];
+
+#[derive(Debug, PartialEq)]
+pub enum RomFormat {
+    Assembly,
+    Binary,
+}
+
+impl RomFormat {
+    // decide on the format by the file extension and fall back on the content when the extension is unknown
+    pub fn detect(filename: &str, content: &[u8]) -> RomFormat {
+        let extension = Path::new(filename)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+        if let Some(extension) = extension {
+            if BINARY_EXTENSIONS.contains(&extension.as_str()) {
+                return RomFormat::Binary;
+            }
+            if ASSEMBLY_EXTENSIONS.contains(&extension.as_str()) {
+                return RomFormat::Assembly;
+            }
+        }
+        // assembly is plain text, anything with control characters is a binary
+        let is_text = content.iter().all(|byte| byte.is_ascii_graphic() || byte.is_ascii_whitespace());
+        if is_text {
+            RomFormat::Assembly
+        } else {
+            RomFormat::Binary
+        }
+    }
+}
+
 pub struct Cartridge {}
 
 impl Cartridge {
-    pub fn read(filename: &str, ram: &mut RAM) {
+    pub fn read(filename: &str, ram: &mut RAM) -> Result<(), String> {
+        let content = match fs::read(filename) {
+            Ok(content) => content,
+            Err(e) => return Err(format!("Failed to read {}: {}", filename, e))
+        };
+        match RomFormat::detect(filename, &content) {
+            RomFormat::Binary => Cartridge::load_binary(&content, ram),
+            RomFormat::Assembly => Cartridge::load_assembly(filename, &content, ram)
+        }
+    }
+
+    // copy a raw ROM into memory as is
+    pub fn load_binary(content: &[u8], ram: &mut RAM) -> Result<(), String> {
+        let available = ram.size() - RAM_OFFSET;
+        if content.len() > available {
+            return Err(format!("ROM is {} bytes, but only {} bytes are available", content.len(), available));
+        }
+        ram.sets(RAM_OFFSET, content);
+        Ok(())
+    }
+
+    fn load_assembly(filename: &str, content: &[u8], ram: &mut RAM) -> Result<(), String> {
+        let text = String::from_utf8_lossy(content);
         let mut interpreter = Interpreter::new(ram);
-        match read_lines(filename) {
-            Ok(lines) => {
-                for (index, line) in lines.flatten().enumerate(){
-                    match interpreter.interpret_line(&line.trim()){
-                        Ok(_) => (),
-                        Err(message) => panic!("{} at line {} for {}", message, index + 1, line)
-                    }
-                }
+        for (index, line) in text.lines().enumerate(){
+            if let Err(message) = interpreter.interpret_line(line.trim()) {
+                return Err(format!("{} at line {} for {}", message, index + 1, line));
             }
-            Err(e) => {
-                panic!("{}", e);
-            }
-        };
+        }
         interpreter.resolve_references();
-        let mut compiled = File::create(filename.to_owned() + ".cmp").expect("Failed to create compiled file");
+        // the compiled program is written next to the source, which fails in a read-only directory
+        let compiled_filename = filename.to_owned() + ".cmp";
+        let mut compiled = File::create(&compiled_filename)
+            .map_err(|e| format!("Failed to create {}: {}", compiled_filename, e))?;
         let mut current = RAM_OFFSET;
         let mut value: u16;
         let mut val = String::new();
@@ -33,12 +86,57 @@ impl Cartridge {
             val.push_str(format!("{:04X}\n", value).as_str());
             current += 2;
         }
-        compiled.write_all(&val.as_bytes()).expect("Failed to write values to file");
+        compiled.write_all(val.as_bytes()).map_err(|e| format!("Failed to write {}: {}", compiled_filename, e))?;
+        Ok(())
     }
 }
 
-fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
-    where P: AsRef<Path>, {
-    let file = File::open(filename)?;
-    Ok(io::BufReader::new(file).lines())
+
+#[cfg(test)]
+mod tests {
+    use crate::drivers::cartridge::{Cartridge, RomFormat};
+    use crate::ram::{RAM, RAM_OFFSET, RAM_SIZE, XO_RAM_SIZE};
+
+    #[test]
+    fn test_detect_by_extension() {
+        assert_eq!(RomFormat::detect("pong.ch8", b"STV 0 1"), RomFormat::Binary);
+        assert_eq!(RomFormat::detect("PONG.C8", b"STV 0 1"), RomFormat::Binary);
+        assert_eq!(RomFormat::detect("program.txt", &[0x12, 0x00]), RomFormat::Assembly);
+    }
+
+    #[test]
+    fn test_detect_by_content() {
+        assert_eq!(RomFormat::detect("pong", &[0x00, 0xE0, 0x12, 0x00]), RomFormat::Binary);
+        assert_eq!(RomFormat::detect("program", b"// comment\nSTV 0 1\nEXT"), RomFormat::Assembly);
+    }
+
+    #[test]
+    fn test_load_assembly_unwritable_directory() {
+        let mut ram = RAM::new();
+        let result = Cartridge::load_assembly("/nonexistent/program.txt", b"DRW 0 1 5", &mut ram);
+        assert!(result.expect_err("").starts_with("Failed to create /nonexistent/program.txt.cmp: "));
+    }
+
+    #[test]
+    fn test_load_binary() {
+        let mut ram = RAM::new();
+        assert_eq!(Cartridge::load_binary(&[0x00, 0xE0, 0x12, 0x00], &mut ram), Ok(()));
+        assert_eq!(ram.get_u16(RAM_OFFSET), 0x00E0);
+        assert_eq!(ram.get_u16(RAM_OFFSET + 2), 0x1200);
+    }
+
+    #[test]
+    fn test_load_binary_xo_chip() {
+        let mut ram = RAM::with_size(XO_RAM_SIZE);
+        let rom = vec![1u8; RAM_SIZE];
+        assert_eq!(Cartridge::load_binary(&rom, &mut ram), Ok(()));
+        assert_eq!(ram.get(RAM_OFFSET + RAM_SIZE - 1), 1);
+    }
+
+    #[test]
+    fn test_load_binary_too_large() {
+        let mut ram = RAM::new();
+        let rom = vec![0u8; RAM_SIZE - RAM_OFFSET + 1];
+        assert_eq!(Cartridge::load_binary(&rom, &mut ram).expect_err(""), "ROM is 3585 bytes, but only 3584 bytes are available");
+    }
 }
@@ -0,0 +1,404 @@
+use crate::save_state::{StateReader, StateWriter};
+
+// the original CHIP-8 resolution
+pub const LORES_WIDTH: usize = 64;
+pub const LORES_HEIGHT: usize = 32;
+// the high resolution mode of SUPER-CHIP
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
+// XO-CHIP draws on two bitplanes, each pixel holds one bit per plane
+pub const PLANES: usize = 2;
+
+
+#[derive(Clone)]
+pub struct FrameBuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+    // bit mask of the planes that drawing, clearing and scrolling act on
+    planes: u8,
+    // set whenever a pixel might have changed, so the screen is only redrawn when needed
+    dirty: bool,
+}
+
+impl Default for FrameBuffer {
+    fn default() -> Self {
+        FrameBuffer::new()
+    }
+}
+
+impl FrameBuffer {
+    pub fn new() -> Self {
+        FrameBuffer {
+            width: LORES_WIDTH,
+            height: LORES_HEIGHT,
+            pixels: vec![0; LORES_WIDTH * LORES_HEIGHT],
+            planes: 0b01,
+            dirty: true,
+        }
+    }
+
+    pub(crate) fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u16(self.width as u16);
+        writer.write_u16(self.height as u16);
+        writer.write_u8(self.planes);
+        writer.write_bytes(&self.pixels);
+    }
+
+    pub(crate) fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        let width = reader.read_u16()? as usize;
+        let height = reader.read_u16()? as usize;
+        let planes = reader.read_u8()?;
+        let pixels = reader.read_bytes()?;
+        let lores = width == LORES_WIDTH && height == LORES_HEIGHT;
+        let hires = width == HIRES_WIDTH && height == HIRES_HEIGHT;
+        if !(lores || hires) || pixels.len() != width * height {
+            return Err(format!("Invalid {}x{} screen in save state", width, height));
+        }
+        // the renderers index a palette with one color per combination of planes
+        let mask = (1 << PLANES) - 1;
+        if planes & !mask != 0 {
+            return Err(format!("Invalid planes {:#04b} in save state", planes));
+        }
+        if let Some(pixel) = pixels.iter().find(|pixel| **pixel & !mask != 0) {
+            return Err(format!("Invalid pixel {:#04b} in save state", pixel));
+        }
+        self.width = width;
+        self.height = height;
+        self.planes = planes;
+        self.pixels = pixels.to_vec();
+        self.dirty = true;
+        Ok(())
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // switch between the 64x32 and 128x64 resolution, this clears the screen
+    pub fn set_hires(&mut self, hires: bool) {
+        (self.width, self.height) = if hires { (HIRES_WIDTH, HIRES_HEIGHT) } else { (LORES_WIDTH, LORES_HEIGHT) };
+        self.pixels = vec![0; self.width * self.height];
+        self.dirty = true;
+    }
+
+    // select the planes for the following operations, plane 1 is the lowest bit
+    pub fn set_planes(&mut self, planes: u8) {
+        self.planes = planes & ((1 << PLANES) - 1);
+    }
+
+    pub fn plane_count(&self) -> usize {
+        self.planes.count_ones() as usize
+    }
+
+    // clear the selected planes
+    pub fn clear(&mut self) {
+        for pixel in self.pixels.iter_mut() {
+            *pixel &= !self.planes;
+        }
+        self.dirty = true;
+    }
+
+    // true when the screen changed since the last call
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::replace(&mut self.dirty, false)
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        self.pixels[self.width * y + x]
+    }
+
+    // xor an 8 pixel wide sprite onto the screen and return true when any pixel was erased. The start coordinate
+    // always wraps around the screen, pixels that fall off the edge either wrap around as well or are clipped.
+    // With multiple planes selected the rows hold a sprite for every plane, one after the other
+    pub fn draw_sprite(&mut self, x: u8, y: u8, rows: &[u8], clip: bool) -> bool {
+        let rows: Vec<u16> = rows.iter().map(|row| (*row as u16) << 8).collect();
+        self.draw(x, y, &rows, 8, clip)
+    }
+
+    // xor a 16x16 SUPER-CHIP sprite onto the screen
+    pub fn draw_large_sprite(&mut self, x: u8, y: u8, rows: &[u16], clip: bool) -> bool {
+        self.draw(x, y, rows, 16, clip)
+    }
+
+    fn draw(&mut self, x: u8, y: u8, rows: &[u16], sprite_width: usize, clip: bool) -> bool {
+        let plane_count = self.plane_count();
+        if plane_count == 0 || rows.is_empty() {
+            return false;
+        }
+        let mut sprites = rows.chunks(rows.len().div_ceil(plane_count));
+        let mut collision = false;
+        for plane in 0..PLANES {
+            let plane_bit = 1 << plane;
+            if self.planes & plane_bit == 0 {
+                continue;
+            }
+            if let Some(sprite) = sprites.next() {
+                collision |= self.draw_plane(x, y, sprite, sprite_width, clip, plane_bit);
+            }
+        }
+        collision
+    }
+
+    // rows are aligned to the most significant bit
+    fn draw_plane(&mut self, x: u8, y: u8, rows: &[u16], sprite_width: usize, clip: bool, plane_bit: u8) -> bool {
+        let start_x = x as usize % self.width;
+        let start_y = y as usize % self.height;
+        let mut collision = false;
+        for (row, value) in rows.iter().enumerate() {
+            let mut pixel_y = start_y + row;
+            if pixel_y >= self.height {
+                if clip {
+                    break;
+                }
+                pixel_y %= self.height;
+            }
+            for bit in 0..sprite_width {
+                if (value >> (15 - bit)) & 1 == 0 {
+                    continue;
+                }
+                let mut pixel_x = start_x + bit;
+                if pixel_x >= self.width {
+                    if clip {
+                        break;
+                    }
+                    pixel_x %= self.width;
+                }
+                let index = self.width * pixel_y + pixel_x;
+                if self.pixels[index] & plane_bit != 0 {
+                    collision = true;
+                }
+                self.pixels[index] ^= plane_bit;
+                self.dirty = true;
+            }
+        }
+        collision
+    }
+
+    pub fn scroll_down(&mut self, amount: usize) {
+        self.scroll(0, amount as isize);
+    }
+
+    pub fn scroll_right(&mut self, amount: usize) {
+        self.scroll(amount as isize, 0);
+    }
+
+    pub fn scroll_left(&mut self, amount: usize) {
+        self.scroll(-(amount as isize), 0);
+    }
+
+    // move the selected planes, pixels scrolled off the screen are lost
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let old = self.pixels.clone();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let source_x = x as isize - dx;
+                let source_y = y as isize - dy;
+                let in_bounds = source_x >= 0 && source_y >= 0 && (source_x as usize) < self.width && (source_y as usize) < self.height;
+                let moved = if in_bounds { old[self.width * source_y as usize + source_x as usize] } else { 0 };
+                let index = self.width * y + x;
+                self.pixels[index] = (old[index] & !self.planes) | (moved & self.planes);
+            }
+        }
+        self.dirty = true;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::drivers::frame_buffer::{FrameBuffer, HIRES_HEIGHT, HIRES_WIDTH, LORES_HEIGHT, LORES_WIDTH};
+    use crate::save_state::{StateReader, StateWriter};
+
+    #[test]
+    fn test_draw_sprite() {
+        let mut frame_buffer = FrameBuffer::new();
+        assert!(!frame_buffer.draw_sprite(2, 3, &[0b10100000], false));
+        assert_eq!(frame_buffer.get(2, 3), 1);
+        assert_eq!(frame_buffer.get(3, 3), 0);
+        assert_eq!(frame_buffer.get(4, 3), 1);
+    }
+
+    #[test]
+    fn test_draw_sprite_collision() {
+        let mut frame_buffer = FrameBuffer::new();
+        assert!(!frame_buffer.draw_sprite(0, 0, &[0b11000000], false));
+        // overlapping pixel is erased
+        assert!(frame_buffer.draw_sprite(1, 0, &[0b11000000], false));
+        assert_eq!(frame_buffer.get(0, 0), 1);
+        assert_eq!(frame_buffer.get(1, 0), 0);
+        assert_eq!(frame_buffer.get(2, 0), 1);
+    }
+
+    #[test]
+    fn test_draw_sprite_no_collision_when_drawing_over_empty_bits() {
+        let mut frame_buffer = FrameBuffer::new();
+        frame_buffer.draw_sprite(0, 0, &[0b10000000], false);
+        assert!(!frame_buffer.draw_sprite(0, 0, &[0b01000000], false));
+    }
+
+    #[test]
+    fn test_draw_sprite_wraps() {
+        let mut frame_buffer = FrameBuffer::new();
+        frame_buffer.draw_sprite(LORES_WIDTH as u8 - 1, LORES_HEIGHT as u8 - 1, &[0b11000000, 0b11000000], false);
+        assert_eq!(frame_buffer.get(LORES_WIDTH - 1, LORES_HEIGHT - 1), 1);
+        assert_eq!(frame_buffer.get(0, LORES_HEIGHT - 1), 1);
+        assert_eq!(frame_buffer.get(LORES_WIDTH - 1, 0), 1);
+        assert_eq!(frame_buffer.get(0, 0), 1);
+    }
+
+    #[test]
+    fn test_draw_sprite_clips() {
+        let mut frame_buffer = FrameBuffer::new();
+        frame_buffer.draw_sprite(LORES_WIDTH as u8 - 1, LORES_HEIGHT as u8 - 1, &[0b11000000, 0b11000000], true);
+        assert_eq!(frame_buffer.get(LORES_WIDTH - 1, LORES_HEIGHT - 1), 1);
+        assert_eq!(frame_buffer.get(0, LORES_HEIGHT - 1), 0);
+        assert_eq!(frame_buffer.get(LORES_WIDTH - 1, 0), 0);
+        assert_eq!(frame_buffer.get(0, 0), 0);
+    }
+
+    #[test]
+    fn test_draw_sprite_start_coordinate_wraps() {
+        let mut frame_buffer = FrameBuffer::new();
+        frame_buffer.draw_sprite(LORES_WIDTH as u8 + 1, LORES_HEIGHT as u8 + 2, &[0b10000000], true);
+        assert_eq!(frame_buffer.get(1, 2), 1);
+    }
+
+    #[test]
+    fn test_set_hires() {
+        let mut frame_buffer = FrameBuffer::new();
+        frame_buffer.draw_sprite(0, 0, &[0b10000000], false);
+        frame_buffer.set_hires(true);
+        assert_eq!((frame_buffer.width(), frame_buffer.height()), (HIRES_WIDTH, HIRES_HEIGHT));
+        assert_eq!(frame_buffer.get(0, 0), 0);
+        frame_buffer.draw_sprite(100, 60, &[0b10000000], false);
+        assert_eq!(frame_buffer.get(100, 60), 1);
+    }
+
+    #[test]
+    fn test_draw_large_sprite() {
+        let mut frame_buffer = FrameBuffer::new();
+        frame_buffer.set_hires(true);
+        let rows = [0x8001u16; 16];
+        assert!(!frame_buffer.draw_large_sprite(10, 20, &rows, false));
+        assert_eq!(frame_buffer.get(10, 20), 1);
+        assert_eq!(frame_buffer.get(25, 35), 1);
+        assert_eq!(frame_buffer.get(11, 20), 0);
+        assert!(frame_buffer.draw_large_sprite(10, 20, &rows, false));
+        assert_eq!(frame_buffer.get(25, 35), 0);
+    }
+
+    #[test]
+    fn test_scroll_down() {
+        let mut frame_buffer = FrameBuffer::new();
+        frame_buffer.draw_sprite(3, 0, &[0b10000000], false);
+        frame_buffer.scroll_down(4);
+        assert_eq!(frame_buffer.get(3, 0), 0);
+        assert_eq!(frame_buffer.get(3, 4), 1);
+    }
+
+    #[test]
+    fn test_scroll_left_and_right() {
+        let mut frame_buffer = FrameBuffer::new();
+        frame_buffer.draw_sprite(0, 1, &[0b10000000], false);
+        frame_buffer.scroll_right(4);
+        assert_eq!(frame_buffer.get(0, 1), 0);
+        assert_eq!(frame_buffer.get(4, 1), 1);
+        frame_buffer.scroll_left(4);
+        assert_eq!(frame_buffer.get(0, 1), 1);
+        assert_eq!(frame_buffer.get(4, 1), 0);
+        // pixels scrolled off the screen are gone
+        frame_buffer.scroll_left(4);
+        frame_buffer.scroll_right(4);
+        assert_eq!(frame_buffer.get(0, 1), 0);
+    }
+
+    #[test]
+    fn test_draw_sprite_on_second_plane() {
+        let mut frame_buffer = FrameBuffer::new();
+        frame_buffer.set_planes(0b10);
+        assert!(!frame_buffer.draw_sprite(0, 0, &[0b10000000], false));
+        assert_eq!(frame_buffer.get(0, 0), 0b10);
+        assert!(frame_buffer.draw_sprite(0, 0, &[0b10000000], false));
+        assert_eq!(frame_buffer.get(0, 0), 0);
+    }
+
+    #[test]
+    fn test_draw_sprite_on_both_planes() {
+        let mut frame_buffer = FrameBuffer::new();
+        frame_buffer.set_planes(0b11);
+        // first row is the sprite for plane 1, second row for plane 2
+        frame_buffer.draw_sprite(0, 0, &[0b11000000, 0b01100000], false);
+        assert_eq!(frame_buffer.get(0, 0), 0b01);
+        assert_eq!(frame_buffer.get(1, 0), 0b11);
+        assert_eq!(frame_buffer.get(2, 0), 0b10);
+        assert_eq!(frame_buffer.get(0, 1), 0);
+    }
+
+    #[test]
+    fn test_clear_selected_plane() {
+        let mut frame_buffer = FrameBuffer::new();
+        frame_buffer.set_planes(0b11);
+        frame_buffer.draw_sprite(0, 0, &[0b10000000, 0b10000000], false);
+        frame_buffer.set_planes(0b10);
+        frame_buffer.clear();
+        assert_eq!(frame_buffer.get(0, 0), 0b01);
+    }
+
+    #[test]
+    fn test_scroll_selected_plane() {
+        let mut frame_buffer = FrameBuffer::new();
+        frame_buffer.set_planes(0b11);
+        frame_buffer.draw_sprite(0, 0, &[0b10000000, 0b10000000], false);
+        frame_buffer.set_planes(0b01);
+        frame_buffer.scroll_down(1);
+        assert_eq!(frame_buffer.get(0, 0), 0b10);
+        assert_eq!(frame_buffer.get(0, 1), 0b01);
+    }
+
+    #[test]
+    fn test_dirty() {
+        let mut frame_buffer = FrameBuffer::new();
+        assert!(frame_buffer.take_dirty());
+        assert!(!frame_buffer.take_dirty());
+        frame_buffer.draw_sprite(0, 0, &[0x00], false);
+        assert!(!frame_buffer.take_dirty());
+        frame_buffer.draw_sprite(0, 0, &[0x80], false);
+        assert!(frame_buffer.take_dirty());
+        frame_buffer.scroll_left(4);
+        assert!(frame_buffer.take_dirty());
+        frame_buffer.set_hires(true);
+        assert!(frame_buffer.take_dirty());
+    }
+
+    #[test]
+    fn test_load_state_rejects_values_outside_the_planes() {
+        let state = |planes: u8, pixel: u8| {
+            let mut writer = StateWriter::new();
+            writer.write_u16(LORES_WIDTH as u16);
+            writer.write_u16(LORES_HEIGHT as u16);
+            writer.write_u8(planes);
+            let mut pixels = vec![0; LORES_WIDTH * LORES_HEIGHT];
+            pixels[7] = pixel;
+            writer.write_bytes(&pixels);
+            writer.into_bytes()
+        };
+        let mut frame_buffer = FrameBuffer::new();
+        assert_eq!(frame_buffer.load_state(&mut StateReader::new(&state(0b11, 0b11))), Ok(()));
+        assert_eq!(frame_buffer.get(7, 0), 0b11);
+        assert_eq!(
+            frame_buffer.load_state(&mut StateReader::new(&state(0b100, 0))).expect_err(""),
+            "Invalid planes 0b100 in save state"
+        );
+        assert_eq!(
+            frame_buffer.load_state(&mut StateReader::new(&state(0b01, 0xFF))).expect_err(""),
+            "Invalid pixel 0b11111111 in save state"
+        );
+        // nothing changed
+        assert_eq!(frame_buffer.get(7, 0), 0b11);
+    }
+}
@@ -0,0 +1,87 @@
+use crate::drivers::display::Display;
+use crate::drivers::frame_buffer::FrameBuffer;
+
+
+// keeps the screen in memory only, for running programs without a video device
+pub struct HeadlessDisplay {
+    v_ram: FrameBuffer,
+    refreshes: u64,
+}
+
+impl Default for HeadlessDisplay {
+    fn default() -> Self {
+        HeadlessDisplay::new()
+    }
+}
+
+impl HeadlessDisplay {
+    pub fn new() -> Self {
+        HeadlessDisplay {
+            v_ram: FrameBuffer::new(),
+            refreshes: 0,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.v_ram.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.v_ram.height()
+    }
+
+    // one entry per pixel, row by row. A pixel is on when it is set in any plane
+    pub fn bits(&self) -> Vec<bool> {
+        let mut bits = Vec::with_capacity(self.width() * self.height());
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                bits.push(self.v_ram.get(x, y) != 0);
+            }
+        }
+        bits
+    }
+
+    // number of frames that would have been shown
+    pub fn refreshes(&self) -> u64 {
+        self.refreshes
+    }
+}
+
+impl Display for HeadlessDisplay {
+    fn v_ram(&mut self) -> &mut FrameBuffer {
+        &mut self.v_ram
+    }
+
+    fn framebuffer(&self) -> &FrameBuffer {
+        &self.v_ram
+    }
+
+    fn clear_display(&mut self) {
+        self.v_ram.clear();
+    }
+
+    fn refresh(&mut self) {
+        self.refreshes += 1;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::drivers::display::Display;
+    use crate::drivers::headless_display::HeadlessDisplay;
+
+    #[test]
+    fn test_bits() {
+        let mut display = HeadlessDisplay::new();
+        display.v_ram().draw_sprite(1, 1, &[0b1010_0000], false);
+        let bits = display.bits();
+        assert_eq!(bits.len(), 64 * 32);
+        assert_eq!(bits.iter().filter(|bit| **bit).count(), 2);
+        assert!(bits[64 + 1]);
+        assert!(bits[64 + 3]);
+
+        display.clear_display();
+        assert!(display.bits().iter().all(|bit| !bit));
+    }
+}
@@ -0,0 +1,141 @@
+use std::fs;
+
+// the error of a keypad that has no more keys to give, because the user wants to quit or a recording is over
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quit;
+
+// anything that can tell which of the 16 keys are held down
+pub trait Keypad {
+    // the keys for the next frame
+    fn poll(&mut self) -> Result<[bool; 16], Quit>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct KeyEvent {
+    frame: u64,
+    key: usize,
+    pressed: bool,
+}
+
+// replays presses and releases at fixed frames, so runs are the same every time
+pub struct ScriptedKeypad {
+    events: Vec<KeyEvent>,
+    position: usize,
+    frame: u64,
+    keys: [bool; 16],
+}
+
+impl Default for ScriptedKeypad {
+    fn default() -> Self {
+        ScriptedKeypad::new()
+    }
+}
+
+impl ScriptedKeypad {
+    pub fn new() -> Self {
+        ScriptedKeypad {
+            events: Vec::new(),
+            position: 0,
+            frame: 0,
+            keys: [false; 16],
+        }
+    }
+
+    // one event per line: the frame number, the key in hex and press or release. Lines starting with # are ignored
+    //   120 5 press
+    //   125 5 release
+    pub fn parse(script: &str) -> Result<Self, String> {
+        let mut keypad = ScriptedKeypad::new();
+        for (number, line) in script.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = || format!("Invalid key event '{}' on line {}", line, number + 1);
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() != 3 {
+                return Err(error());
+            }
+            let frame = parts[0].parse().map_err(|_| error())?;
+            let key = match u8::from_str_radix(parts[1], 16) {
+                Ok(key) if key < 16 => key,
+                _ => return Err(error())
+            };
+            match parts[2] {
+                "press" => keypad.press(frame, key),
+                "release" => keypad.release(frame, key),
+                _ => return Err(error())
+            }
+        }
+        Ok(keypad)
+    }
+
+    pub fn read(filename: &str) -> Result<Self, String> {
+        match fs::read_to_string(filename) {
+            Ok(script) => ScriptedKeypad::parse(&script),
+            Err(e) => Err(format!("Failed to read {}: {}", filename, e))
+        }
+    }
+
+    pub fn press(&mut self, frame: u64, key: u8) {
+        self.add(KeyEvent { frame, key: key as usize, pressed: true });
+    }
+
+    pub fn release(&mut self, frame: u64, key: u8) {
+        self.add(KeyEvent { frame, key: key as usize, pressed: false });
+    }
+
+    // keeps the events ordered by frame, events on the same frame keep their order
+    fn add(&mut self, event: KeyEvent) {
+        let index = self.events.partition_point(|other| other.frame <= event.frame);
+        self.events.insert(index, event);
+    }
+}
+
+impl Keypad for ScriptedKeypad {
+    fn poll(&mut self) -> Result<[bool; 16], Quit> {
+        while let Some(event) = self.events.get(self.position) {
+            if event.frame > self.frame {
+                break;
+            }
+            self.keys[event.key] = event.pressed;
+            self.position += 1;
+        }
+        self.frame += 1;
+        Ok(self.keys)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::drivers::keypad::{Keypad, ScriptedKeypad};
+
+    fn pressed(keys: [bool; 16]) -> Vec<usize> {
+        (0..16).filter(|key| keys[*key]).collect()
+    }
+
+    #[test]
+    fn test_scripted_keypad() {
+        let mut keypad = ScriptedKeypad::new();
+        keypad.release(3, 0xA);
+        keypad.press(1, 0xA);
+        keypad.press(2, 0x5);
+        assert_eq!(pressed(keypad.poll().unwrap()), []);
+        assert_eq!(pressed(keypad.poll().unwrap()), [0xA]);
+        assert_eq!(pressed(keypad.poll().unwrap()), [0x5, 0xA]);
+        assert_eq!(pressed(keypad.poll().unwrap()), [0x5]);
+        assert_eq!(pressed(keypad.poll().unwrap()), [0x5]);
+    }
+
+    #[test]
+    fn test_parse_script() {
+        let mut keypad = ScriptedKeypad::parse("# start the game\n0 f press\n\n1 F release\n").unwrap();
+        assert_eq!(pressed(keypad.poll().unwrap()), [0xF]);
+        assert_eq!(pressed(keypad.poll().unwrap()), []);
+
+        assert_eq!(ScriptedKeypad::parse("0 10 press").err().unwrap(), "Invalid key event '0 10 press' on line 1");
+        assert_eq!(ScriptedKeypad::parse("\n0 1 hold").err().unwrap(), "Invalid key event '0 1 hold' on line 2");
+        assert_eq!(ScriptedKeypad::parse("soon 1 press").err().unwrap(), "Invalid key event 'soon 1 press' on line 1");
+    }
+}
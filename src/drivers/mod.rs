@@ -1,7 +1,13 @@
 mod display;
-mod input;
+mod headless_display;
+mod keypad;
 mod cartridge;
+mod frame_buffer;
+mod screenshot;
 
 pub use self::display::Display;
-pub use self::input::Input;
+pub use self::headless_display::HeadlessDisplay;
+pub use self::frame_buffer::{FrameBuffer, HIRES_HEIGHT, HIRES_WIDTH, LORES_HEIGHT, LORES_WIDTH};
+pub use self::keypad::{Keypad, Quit, ScriptedKeypad};
 pub use self::cartridge::Cartridge;
+pub use self::screenshot::{encode_screenshot, next_filename, save_screenshot, ImageFormat};
@@ -0,0 +1,205 @@
+use std::fs;
+use std::path::Path;
+use crate::drivers::frame_buffer::{FrameBuffer, LORES_WIDTH};
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+// the most a stored deflate block can hold
+const MAX_STORED_BLOCK: usize = 0xFFFF;
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    // in the colors of the palette
+    Png,
+    // 1 bit per pixel, lit pixels are black
+    Pbm,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Pbm => "pbm",
+        }
+    }
+}
+
+// scale is the size of a lores pixel, hires pixels are half as big so both modes give the same image size
+pub fn encode_screenshot(frame_buffer: &FrameBuffer, format: ImageFormat, palette: &[(u8, u8, u8); 4], scale: u32) -> Vec<u8> {
+    match format {
+        ImageFormat::Png => encode_png(frame_buffer, palette, scale),
+        ImageFormat::Pbm => encode_pbm(frame_buffer),
+    }
+}
+
+// the first of prefix-001.png, prefix-002.png, ... that doesn't exist yet
+pub fn next_filename(prefix: &str, extension: &str) -> String {
+    let mut number = 1;
+    loop {
+        let filename = format!("{}-{:03}.{}", prefix, number, extension);
+        if !Path::new(&filename).exists() {
+            return filename;
+        }
+        number += 1;
+    }
+}
+
+pub fn save_screenshot(filename: &str, frame_buffer: &FrameBuffer, format: ImageFormat, palette: &[(u8, u8, u8); 4], scale: u32) -> Result<(), String> {
+    match fs::write(filename, encode_screenshot(frame_buffer, format, palette, scale)) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Failed to write {}: {}", filename, e))
+    }
+}
+
+fn encode_pbm(frame_buffer: &FrameBuffer) -> Vec<u8> {
+    let mut bytes = format!("P4\n{} {}\n", frame_buffer.width(), frame_buffer.height()).into_bytes();
+    for y in 0..frame_buffer.height() {
+        // rows are padded to whole bytes, the widths are multiples of 8 anyway
+        for byte_x in (0..frame_buffer.width()).step_by(8) {
+            let mut byte = 0;
+            for bit in 0..8 {
+                if byte_x + bit < frame_buffer.width() && frame_buffer.get(byte_x + bit, y) != 0 {
+                    byte |= 0x80 >> bit;
+                }
+            }
+            bytes.push(byte);
+        }
+    }
+    bytes
+}
+
+// 8 bit RGB without compression, a CHIP-8 screen is small enough that it doesn't matter
+fn encode_png(frame_buffer: &FrameBuffer, palette: &[(u8, u8, u8); 4], scale: u32) -> Vec<u8> {
+    let pixel_size = (scale as usize * LORES_WIDTH / frame_buffer.width()).max(1);
+    let width = frame_buffer.width() * pixel_size;
+    let height = frame_buffer.height() * pixel_size;
+
+    // every row starts with the filter type, 0 is none
+    let mut image = Vec::with_capacity((width * 3 + 1) * height);
+    for y in 0..height {
+        image.push(0);
+        for x in 0..width {
+            let (r, g, b) = palette[frame_buffer.get(x / pixel_size, y / pixel_size) as usize];
+            image.extend_from_slice(&[r, g, b]);
+        }
+    }
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // bit depth 8, color type RGB, default compression, filter and no interlacing
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut png = PNG_SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&image));
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+// a zlib stream of uncompressed deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        stream.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        stream.push(last as u8);
+        let length = block.len() as u16;
+        stream.extend_from_slice(&length.to_le_bytes());
+        stream.extend_from_slice(&(!length).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFF;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::drivers::frame_buffer::FrameBuffer;
+    use crate::drivers::screenshot::{adler32, crc32, encode_screenshot, zlib_stored, ImageFormat};
+
+    const PALETTE: [(u8, u8, u8); 4] = [(0, 0, 0), (255, 255, 255), (1, 1, 1), (2, 2, 2)];
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn test_zlib_stored() {
+        let stream = zlib_stored(b"abc");
+        assert_eq!(stream, [0x78, 0x01, 1, 3, 0, 0xFC, 0xFF, b'a', b'b', b'c', 0x02, 0x4D, 0x01, 0x27]);
+        // split into blocks of at most 65535 bytes
+        let stream = zlib_stored(&vec![0; 70000]);
+        assert_eq!(stream[2], 0);
+        assert_eq!(stream[2 + 5 + 0xFFFF], 1);
+        assert_eq!(stream.len(), 2 + 5 + 0xFFFF + 5 + (70000 - 0xFFFF) + 4);
+    }
+
+    #[test]
+    fn test_pbm() {
+        let mut frame_buffer = FrameBuffer::new();
+        frame_buffer.draw_sprite(1, 0, &[0b1100_0000], false);
+        let pbm = encode_screenshot(&frame_buffer, ImageFormat::Pbm, &PALETTE, 1);
+        let header = b"P4\n64 32\n";
+        assert_eq!(&pbm[..header.len()], header);
+        assert_eq!(pbm.len(), header.len() + 8 * 32);
+        assert_eq!(pbm[header.len()], 0b0110_0000);
+        assert_eq!(pbm[header.len() + 1], 0);
+    }
+
+    #[test]
+    fn test_png() {
+        let mut frame_buffer = FrameBuffer::new();
+        frame_buffer.draw_sprite(0, 0, &[0x80], false);
+        let png = encode_screenshot(&frame_buffer, ImageFormat::Png, &PALETTE, 2);
+        assert_eq!(&png[..8], [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n']);
+        assert_eq!(&png[12..16], b"IHDR");
+        // 128x64 for a scale of 2
+        assert_eq!(&png[16..24], [0, 0, 0, 128, 0, 0, 0, 64]);
+        assert_eq!(&png[png.len() - 12..], [0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]);
+        // the first row, the lit pixel is 2 pixels wide
+        let data = &png[33 + 8 + 2 + 5..];
+        assert_eq!(&data[..10], [0, 255, 255, 255, 255, 255, 255, 0, 0, 0]);
+
+        // hires pixels are half the size
+        frame_buffer.set_hires(true);
+        let png = encode_screenshot(&frame_buffer, ImageFormat::Png, &PALETTE, 2);
+        assert_eq!(&png[16..24], [0, 0, 0, 128, 0, 0, 0, 64]);
+    }
+}
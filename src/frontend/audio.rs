@@ -0,0 +1,177 @@
+extern crate sdl2;
+
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+
+
+pub const DEFAULT_PITCH: f32 = 440.0;
+pub const DEFAULT_VOLUME: f32 = 0.25;
+const SAMPLE_RATE: i32 = 44100;
+// higher tones can't be played at the sample rate
+pub const MAX_PITCH: f32 = (SAMPLE_RATE / 2) as f32;
+
+
+// a device that can sound the buzzer while the sound timer is running
+pub trait Beeper {
+    fn set_playing(&mut self, playing: bool);
+
+    // play the 128 bit XO-CHIP pattern instead of the square wave, pitch is the value of the pitch register.
+    // Without a pattern it is the square wave again, after a state without one was loaded
+    fn set_pattern(&mut self, pattern: Option<&[u8; 16]>, pitch: u8);
+}
+
+// playback rate of an XO-CHIP audio pattern in bits per second
+fn pattern_rate(pitch: u8) -> f32 {
+    4000.0 * 2f32.powf((pitch as f32 - 64.0) / 48.0)
+}
+
+struct Tone {
+    // fraction of a wave period that passes with every sample
+    phase_inc: f32,
+    phase: f32,
+    volume: f32,
+    sample_rate: f32,
+    pattern: Option<[u8; 16]>,
+    // bits of the pattern that pass with every sample
+    pattern_inc: f32,
+    pattern_position: f32,
+}
+
+impl Tone {
+    fn sample(&mut self) -> f32 {
+        let high = match &self.pattern {
+            Some(pattern) => {
+                let bit = self.pattern_position as usize;
+                self.pattern_position = (self.pattern_position + self.pattern_inc) % 128.0;
+                (pattern[bit / 8] >> (7 - bit % 8)) & 1 == 1
+            }
+            None => {
+                let high = self.phase < 0.5;
+                self.phase = (self.phase + self.phase_inc) % 1.0;
+                high
+            }
+        };
+        if high { self.volume } else { -self.volume }
+    }
+}
+
+impl AudioCallback for Tone {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        for sample in out.iter_mut() {
+            *sample = self.sample();
+        }
+    }
+}
+
+pub struct Audio {
+    device: AudioDevice<Tone>,
+    playing: bool,
+    // last pattern and pitch handed to the audio thread
+    pattern: Option<([u8; 16], u8)>,
+}
+
+impl Audio {
+    pub fn new(sdl_context: &sdl2::Sdl, pitch: f32, volume: f32) -> Result<Self, String> {
+        let audio_subsystem = sdl_context.audio()?;
+        let desired_spec = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE),
+            channels: Some(1),
+            samples: None,
+        };
+        let device = audio_subsystem.open_playback(None, &desired_spec, |spec| {
+            Tone {
+                phase_inc: pitch / spec.freq as f32,
+                phase: 0.0,
+                volume: volume.clamp(0.0, 1.0),
+                sample_rate: spec.freq as f32,
+                pattern: None,
+                pattern_inc: 0.0,
+                pattern_position: 0.0,
+            }
+        })?;
+        Ok(Audio {
+            device,
+            playing: false,
+            pattern: None,
+        })
+    }
+}
+
+impl Beeper for Audio {
+    fn set_playing(&mut self, playing: bool) {
+        if playing == self.playing {
+            return;
+        }
+        if playing {
+            self.device.resume();
+        } else {
+            self.device.pause();
+        }
+        self.playing = playing;
+    }
+
+    fn set_pattern(&mut self, pattern: Option<&[u8; 16]>, pitch: u8) {
+        let pattern = pattern.map(|pattern| (*pattern, pitch));
+        if self.pattern == pattern {
+            return;
+        }
+        self.pattern = pattern;
+        let mut tone = self.device.lock();
+        tone.pattern_inc = pattern_rate(pitch) / tone.sample_rate;
+        tone.pattern = pattern.map(|(pattern, _)| pattern);
+    }
+}
+
+// silent audio for headless runs and tests
+pub struct NullAudio {}
+
+impl Beeper for NullAudio {
+    fn set_playing(&mut self, _playing: bool) {}
+
+    fn set_pattern(&mut self, _pattern: Option<&[u8; 16]>, _pitch: u8) {}
+}
+
+
+#[cfg(test)]
+mod tests {
+    use sdl2::audio::AudioCallback;
+    use crate::frontend::audio::{pattern_rate, Tone};
+
+    fn tone(pattern: Option<[u8; 16]>) -> Tone {
+        Tone {
+            phase_inc: 0.25,
+            phase: 0.0,
+            volume: 0.5,
+            sample_rate: 8000.0,
+            pattern,
+            pattern_inc: 0.5,
+            pattern_position: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_square_wave() {
+        let mut wave = tone(None);
+        let mut out = [0.0; 8];
+        wave.callback(&mut out);
+        assert_eq!(out, [0.5, 0.5, -0.5, -0.5, 0.5, 0.5, -0.5, -0.5]);
+    }
+
+    #[test]
+    fn test_pattern() {
+        let mut pattern = [0; 16];
+        pattern[0] = 0b10100000;
+        let mut wave = tone(Some(pattern));
+        let mut out = [0.0; 8];
+        wave.callback(&mut out);
+        // every bit lasts two samples
+        assert_eq!(out, [0.5, 0.5, -0.5, -0.5, 0.5, 0.5, -0.5, -0.5]);
+    }
+
+    #[test]
+    fn test_pattern_rate() {
+        assert_eq!(pattern_rate(64), 4000.0);
+        assert_eq!(pattern_rate(112), 8000.0);
+    }
+}
@@ -0,0 +1,163 @@
+extern crate sdl2;
+
+use sdl2::pixels;
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, Texture};
+use sdl2::video::Window;
+use chippie_ate::{Display, FrameBuffer, HIRES_HEIGHT, HIRES_WIDTH, LORES_HEIGHT, LORES_WIDTH};
+use crate::frontend::renderer::{RenderMode, Renderer};
+
+
+// size of a pixel in the 64x32 resolution, hires pixels are half this size
+pub const DEFAULT_SCALE: u32 = 20;
+// indexed by the planes that are set for a pixel, only the first two are used without XO-CHIP
+const GREEN: [pixels::Color; 4] = [
+    pixels::Color::RGB(0, 0, 0),
+    pixels::Color::RGB(0, 250, 0),
+    pixels::Color::RGB(0, 110, 0),
+    pixels::Color::RGB(200, 255, 200)
+];
+const WHITE: [pixels::Color; 4] = [
+    pixels::Color::RGB(0, 0, 0),
+    pixels::Color::RGB(255, 255, 255),
+    pixels::Color::RGB(170, 170, 170),
+    pixels::Color::RGB(85, 85, 85)
+];
+const AMBER: [pixels::Color; 4] = [
+    pixels::Color::RGB(20, 10, 0),
+    pixels::Color::RGB(255, 176, 0),
+    pixels::Color::RGB(150, 90, 0),
+    pixels::Color::RGB(255, 220, 140)
+];
+// the four shades of the original Game Boy screen
+const LCD: [pixels::Color; 4] = [
+    pixels::Color::RGB(155, 188, 15),
+    pixels::Color::RGB(15, 56, 15),
+    pixels::Color::RGB(48, 98, 48),
+    pixels::Color::RGB(139, 172, 15)
+];
+// the defaults of the Octo IDE
+const OCTO: [pixels::Color; 4] = [
+    pixels::Color::RGB(0x99, 0x66, 0x00),
+    pixels::Color::RGB(0xFF, 0xCC, 0x00),
+    pixels::Color::RGB(0xFF, 0x66, 0x00),
+    pixels::Color::RGB(0x66, 0x22, 0x00)
+];
+pub const PALETTE_NAMES: [&str; 5] = ["green", "white", "amber", "lcd", "octo"];
+
+
+pub fn palette(name: &str) -> Option<[pixels::Color; 4]> {
+    match name {
+        "green" => Some(GREEN),
+        "white" => Some(WHITE),
+        "amber" => Some(AMBER),
+        "lcd" => Some(LCD),
+        "octo" => Some(OCTO),
+        _ => None
+    }
+}
+
+// how the window looks
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisplayOptions {
+    pub title: String,
+    // background, foreground and the two extra XO-CHIP colors
+    pub palette: [pixels::Color; 4],
+    pub scale: u32,
+    pub fullscreen: bool,
+    pub resizable: bool,
+    pub render_mode: RenderMode,
+}
+
+impl Default for DisplayOptions {
+    fn default() -> Self {
+        DisplayOptions {
+            title: "Chip-8 system".to_string(),
+            palette: GREEN,
+            scale: DEFAULT_SCALE,
+            fullscreen: false,
+            resizable: false,
+            render_mode: RenderMode::Plain,
+        }
+    }
+}
+
+
+pub struct SdlDisplay {
+    canvas: Canvas<Window>,
+    // big enough for the hires screen, the lores screen only uses the top left corner
+    texture: Texture,
+    v_ram: FrameBuffer,
+    palette: [pixels::Color; 4],
+    renderer: Renderer
+}
+
+impl SdlDisplay {
+    pub fn new(sdl_context: &sdl2::Sdl, options: &DisplayOptions) -> Self {
+        let video_subsystem = sdl_context.video().unwrap();
+
+        let width = LORES_WIDTH as u32 * options.scale;
+        let height = LORES_HEIGHT as u32 * options.scale;
+        let mut window = video_subsystem.window(&options.title, width, height);
+        window.position_centered();
+        if options.fullscreen {
+            window.fullscreen_desktop();
+        }
+        if options.resizable {
+            window.resizable();
+        }
+        let mut canvas = window.build().unwrap().into_canvas().build().unwrap();
+        // the screen is drawn with one unit per pixel, SDL scales it to the window and adds black bars
+        // when the window doesn't have the same aspect ratio
+        let _ = canvas.set_logical_size(LORES_WIDTH as u32, LORES_HEIGHT as u32);
+        canvas.set_draw_color(pixels::Color::RGB(0, 0, 0));
+        canvas.clear();
+        canvas.present();
+        let texture = canvas.texture_creator()
+            .create_texture_streaming(pixels::PixelFormatEnum::RGB24, HIRES_WIDTH as u32, HIRES_HEIGHT as u32)
+            .unwrap();
+        let v_ram = FrameBuffer::new();
+        SdlDisplay {
+            canvas,
+            texture,
+            v_ram,
+            palette: options.palette,
+            renderer: Renderer::new(options.render_mode)
+        }
+    }
+}
+
+impl Display for SdlDisplay {
+    fn clear_display(&mut self){
+        self.v_ram.clear();
+    }
+
+    fn v_ram(&mut self) -> &mut FrameBuffer {
+        &mut self.v_ram
+    }
+
+    fn framebuffer(&self) -> &FrameBuffer {
+        &self.v_ram
+    }
+
+    // called once per frame, the texture is only uploaded again when the screen changed
+    fn refresh(&mut self){
+        let (width, height) = (self.v_ram.width() as u32, self.v_ram.height() as u32);
+        let area = Rect::new(0, 0, width, height);
+        if self.v_ram.take_dirty() || self.renderer.changing() {
+            let colors = self.renderer.colors(&self.v_ram, &self.palette);
+            let mut bytes = Vec::with_capacity(colors.len() * 3);
+            for color in colors {
+                bytes.extend_from_slice(&[color.r, color.g, color.b]);
+            }
+            let _ = self.texture.update(area, &bytes, width as usize * 3);
+        }
+        if self.canvas.logical_size() != (width, height) {
+            let _ = self.canvas.set_logical_size(width, height);
+        }
+        self.canvas.set_draw_color(pixels::Color::RGB(0, 0, 0));
+        self.canvas.clear();
+        let _ = self.canvas.copy(&self.texture, area, None);
+        self.canvas.present();
+    }
+}
@@ -0,0 +1,336 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use sdl2::pixels::Color;
+use chippie_ate::{FrameBuffer, FRAME_RATE, LORES_HEIGHT, LORES_WIDTH};
+
+// size of a lores pixel in the GIF, hires pixels are half this size
+pub const GIF_SCALE: usize = 4;
+// the four palette entries need 2 bit codes
+const MIN_CODE_SIZE: u8 = 2;
+const MAX_CODE_SIZE: u8 = 12;
+// browsers slow down frames shorter than this to a tenth of a second, in hundredths of a second
+const MIN_DELAY: u64 = 2;
+
+
+// writes every frame that is shown to an animated GIF. The frames of the GIF can't be shorter than 2/100 of a
+// second, so a frame that is replaced sooner is skipped while the total time stays exact
+pub struct GifRecorder<W: Write> {
+    writer: W,
+    frames: u64,
+    // the last frame and the frame number it was shown at, it is written once its length is known
+    pending: Option<(Vec<u8>, u64)>,
+}
+
+impl GifRecorder<BufWriter<File>> {
+    pub fn create(filename: &str, palette: &[Color; 4]) -> Result<Self, String> {
+        match File::create(filename) {
+            Ok(file) => GifRecorder::new(BufWriter::new(file), palette),
+            Err(e) => Err(format!("Failed to write {}: {}", filename, e))
+        }
+    }
+}
+
+impl<W: Write> GifRecorder<W> {
+    pub fn new(writer: W, palette: &[Color; 4]) -> Result<Self, String> {
+        let mut recorder = GifRecorder { writer, frames: 0, pending: None };
+        let mut header = b"GIF89a".to_vec();
+        header.extend_from_slice(&((LORES_WIDTH * GIF_SCALE) as u16).to_le_bytes());
+        header.extend_from_slice(&((LORES_HEIGHT * GIF_SCALE) as u16).to_le_bytes());
+        // global color table with 4 entries, background color 0, square pixels
+        header.extend_from_slice(&[0x91, 0, 0]);
+        for color in palette {
+            header.extend_from_slice(&[color.r, color.g, color.b]);
+        }
+        // loop forever
+        header.extend_from_slice(&[0x21, 0xFF, 0x0B]);
+        header.extend_from_slice(b"NETSCAPE2.0");
+        header.extend_from_slice(&[0x03, 0x01, 0x00, 0x00, 0x00]);
+        recorder.write(&header)?;
+        Ok(recorder)
+    }
+
+    // called once for every frame at 60 Hz
+    pub fn add_frame(&mut self, frame_buffer: &FrameBuffer) -> Result<(), String> {
+        let pixels = scaled_pixels(frame_buffer);
+        let frame = self.frames;
+        self.frames += 1;
+        self.pending = match self.pending.take() {
+            None => Some((pixels, frame)),
+            Some((pending, start)) if pending == pixels => Some((pending, start)),
+            Some((pending, start)) => {
+                let delay = centiseconds(frame) - centiseconds(start);
+                if delay < MIN_DELAY {
+                    Some((pixels, start))
+                } else {
+                    self.write_frame(&pending, delay)?;
+                    Some((pixels, frame))
+                }
+            }
+        };
+        Ok(())
+    }
+
+    // write the last frame and the end of the file
+    pub fn finish(mut self) -> Result<W, String> {
+        if let Some((pending, start)) = self.pending.take() {
+            let delay = (centiseconds(self.frames) - centiseconds(start)).max(MIN_DELAY);
+            self.write_frame(&pending, delay)?;
+        }
+        self.write(&[0x3B])?;
+        self.writer.flush().map_err(|e| format!("Failed to write GIF: {}", e))?;
+        Ok(self.writer)
+    }
+
+    fn write_frame(&mut self, pixels: &[u8], delay: u64) -> Result<(), String> {
+        let width = (LORES_WIDTH * GIF_SCALE) as u16;
+        let height = (LORES_HEIGHT * GIF_SCALE) as u16;
+        // graphic control extension with the delay
+        let mut bytes = vec![0x21, 0xF9, 0x04, 0x04];
+        bytes.extend_from_slice(&(delay as u16).to_le_bytes());
+        bytes.extend_from_slice(&[0x00, 0x00]);
+        // image descriptor covering the whole screen, using the global color table
+        bytes.push(0x2C);
+        bytes.extend_from_slice(&[0, 0, 0, 0]);
+        bytes.extend_from_slice(&width.to_le_bytes());
+        bytes.extend_from_slice(&height.to_le_bytes());
+        bytes.push(0);
+        bytes.push(MIN_CODE_SIZE);
+        for block in lzw_encode(pixels).chunks(255) {
+            bytes.push(block.len() as u8);
+            bytes.extend_from_slice(block);
+        }
+        bytes.push(0);
+        self.write(&bytes)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.writer.write_all(bytes).map_err(|e| format!("Failed to write GIF: {}", e))
+    }
+}
+
+// time of a frame in hundredths of a second
+fn centiseconds(frame: u64) -> u64 {
+    (frame * 100 + FRAME_RATE as u64 / 2) / FRAME_RATE as u64
+}
+
+// palette indexes of the whole GIF, row by row
+fn scaled_pixels(frame_buffer: &FrameBuffer) -> Vec<u8> {
+    let width = LORES_WIDTH * GIF_SCALE;
+    let height = LORES_HEIGHT * GIF_SCALE;
+    let pixel_size = width / frame_buffer.width();
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            pixels.push(frame_buffer.get(x / pixel_size, y / pixel_size));
+        }
+    }
+    pixels
+}
+
+// the variable length LZW of GIF, codes are packed starting at the lowest bit
+fn lzw_encode(pixels: &[u8]) -> Vec<u8> {
+    let clear_code: u16 = 1 << MIN_CODE_SIZE;
+    let end_code = clear_code + 1;
+    let mut output = BitWriter::new();
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut code_size = MIN_CODE_SIZE + 1;
+    let mut next_code = end_code + 1;
+    output.write(clear_code, code_size);
+
+    let mut pixels = pixels.iter();
+    let mut prefix = match pixels.next() {
+        Some(pixel) => *pixel as u16,
+        None => {
+            output.write(end_code, code_size);
+            return output.finish();
+        }
+    };
+    for pixel in pixels {
+        if let Some(code) = table.get(&(prefix, *pixel)) {
+            prefix = *code;
+            continue;
+        }
+        output.write(prefix, code_size);
+        // the decoder learns about a code one step later, so the size grows once the next code doesn't fit
+        if next_code == 1 << code_size && code_size < MAX_CODE_SIZE {
+            code_size += 1;
+        }
+        if next_code < 1 << MAX_CODE_SIZE {
+            table.insert((prefix, *pixel), next_code);
+            next_code += 1;
+        } else {
+            // the table is full, start over
+            output.write(clear_code, code_size);
+            table.clear();
+            code_size = MIN_CODE_SIZE + 1;
+            next_code = end_code + 1;
+        }
+        prefix = *pixel as u16;
+    }
+    output.write(prefix, code_size);
+    if next_code == 1 << code_size && code_size < MAX_CODE_SIZE {
+        code_size += 1;
+    }
+    output.write(end_code, code_size);
+    output.finish()
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u8,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        BitWriter { bytes: Vec::new(), buffer: 0, bits: 0 }
+    }
+
+    fn write(&mut self, code: u16, size: u8) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += size;
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use sdl2::pixels::Color;
+    use chippie_ate::FrameBuffer;
+    use crate::frontend::gif::{centiseconds, lzw_encode, GifRecorder, GIF_SCALE, MAX_CODE_SIZE, MIN_CODE_SIZE};
+
+    const PALETTE: [Color; 4] = [
+        Color::RGB(0, 0, 0),
+        Color::RGB(255, 255, 255),
+        Color::RGB(255, 0, 0),
+        Color::RGB(0, 0, 255)
+    ];
+
+    // a plain GIF decoder, following the specification
+    fn lzw_decode(data: &[u8]) -> Vec<u8> {
+        let clear_code = 1usize << MIN_CODE_SIZE;
+        let end_code = clear_code + 1;
+        let initial: Vec<Vec<u8>> = (0..clear_code + 2).map(|code| vec![code as u8]).collect();
+        let mut table = initial.clone();
+        let mut code_size = MIN_CODE_SIZE + 1;
+        let mut previous: Option<usize> = None;
+        let mut output = Vec::new();
+        let mut position = 0;
+        loop {
+            let mut code = 0;
+            for bit in 0..code_size as usize {
+                let index = position + bit;
+                code |= (((data[index / 8] >> (index % 8)) & 1) as usize) << bit;
+            }
+            position += code_size as usize;
+            if code == clear_code {
+                table = initial.clone();
+                code_size = MIN_CODE_SIZE + 1;
+                previous = None;
+                continue;
+            }
+            if code == end_code {
+                return output;
+            }
+            let entry = match previous {
+                None => table[code].clone(),
+                Some(previous) => {
+                    let entry = if code < table.len() {
+                        table[code].clone()
+                    } else {
+                        let mut entry = table[previous].clone();
+                        entry.push(entry[0]);
+                        entry
+                    };
+                    let mut new_entry = table[previous].clone();
+                    new_entry.push(entry[0]);
+                    table.push(new_entry);
+                    if table.len() == 1 << code_size && code_size < MAX_CODE_SIZE {
+                        code_size += 1;
+                    }
+                    entry
+                }
+            };
+            output.extend_from_slice(&entry);
+            previous = Some(code);
+        }
+    }
+
+    #[test]
+    fn test_lzw_round_trip() {
+        let short = vec![1, 1, 1, 1, 2, 3, 0, 1, 1, 1];
+        assert_eq!(lzw_decode(&lzw_encode(&short)), short);
+
+        // enough noise to fill the table and start over a few times
+        let mut value: u32 = 12345;
+        let noise: Vec<u8> = (0..50000).map(|_| {
+            value = value.wrapping_mul(1103515245).wrapping_add(12345);
+            ((value >> 16) % 4) as u8
+        }).collect();
+        assert_eq!(lzw_decode(&lzw_encode(&noise)), noise);
+
+        let flat = vec![0; 128 * 256];
+        let encoded = lzw_encode(&flat);
+        assert!(encoded.len() < 1000);
+        assert_eq!(lzw_decode(&encoded), flat);
+    }
+
+    #[test]
+    fn test_centiseconds() {
+        assert_eq!(centiseconds(0), 0);
+        assert_eq!(centiseconds(1), 2);
+        assert_eq!(centiseconds(3), 5);
+        assert_eq!(centiseconds(60), 100);
+    }
+
+    // the delays of all frames in a GIF
+    fn delays(gif: &[u8]) -> Vec<u16> {
+        let mut delays = Vec::new();
+        for index in 0..gif.len() - 6 {
+            if gif[index..index + 4] == [0x21, 0xF9, 0x04, 0x04] {
+                delays.push(u16::from_le_bytes([gif[index + 4], gif[index + 5]]));
+            }
+        }
+        delays
+    }
+
+    #[test]
+    fn test_recording() {
+        let mut recorder = GifRecorder::new(Vec::new(), &PALETTE).unwrap();
+        let mut frame_buffer = FrameBuffer::new();
+        // 30 still frames, then a change every frame for 6 frames and 24 still frames
+        for _ in 0..30 {
+            recorder.add_frame(&frame_buffer).unwrap();
+        }
+        for _ in 0..6 {
+            frame_buffer.draw_sprite(0, 0, &[0x80], false);
+            recorder.add_frame(&frame_buffer).unwrap();
+        }
+        for _ in 0..24 {
+            recorder.add_frame(&frame_buffer).unwrap();
+        }
+        let gif = recorder.finish().unwrap();
+        assert_eq!(&gif[..6], b"GIF89a");
+        assert_eq!(&gif[6..10], [(64 * GIF_SCALE) as u8, 1, (32 * GIF_SCALE) as u8, 0]);
+        assert_eq!(&gif[13..16], [0, 0, 0]);
+        assert_eq!(gif[gif.len() - 1], 0x3B);
+        let delays = delays(&gif);
+        // short frames are merged, but the whole clip is still a second long
+        assert_eq!(delays.iter().map(|delay| *delay as u32).sum::<u32>(), 100);
+        assert!(delays.iter().all(|delay| *delay >= 2));
+        assert_eq!(delays[0], 50);
+    }
+}
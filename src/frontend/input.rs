@@ -0,0 +1,181 @@
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::Event;
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::GameControllerSubsystem;
+use chippie_ate::{Keypad, Quit};
+use chippie_ate::ImageFormat;
+use crate::keymap::Keymap;
+
+
+// thanks to https://github.com/starrhorne/chip8-rust
+
+// F1-F9 load the slot with the same number, holding shift saves to it
+const SLOT_KEYS: [Keycode; 9] = [
+    Keycode::F1, Keycode::F2, Keycode::F3,
+    Keycode::F4, Keycode::F5, Keycode::F6,
+    Keycode::F7, Keycode::F8, Keycode::F9
+];
+
+// how far a stick has to be pushed before it counts as a press, about half way
+const AXIS_THRESHOLD: i16 = 16384;
+
+// a control on a game controller
+enum PadControl {
+    Button(Button),
+    // the axis and whether it is pushed to the positive side
+    Axis(Axis, bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Hotkey {
+    SaveState(u8),
+    LoadState(u8),
+    // F12 for a PNG, shift F12 for a PBM
+    Screenshot(ImageFormat),
+    // F11 starts and stops recording a GIF
+    ToggleGif,
+}
+
+pub struct Input {
+    events: sdl2::EventPump,
+    // keyboard key and the CHIP-8 key it presses
+    bindings: Vec<(Keycode, usize)>,
+    pad_bindings: Vec<(PadControl, usize)>,
+    // missing when SDL can't use controllers, the controllers are opened as they are plugged in
+    controller_subsystem: Option<GameControllerSubsystem>,
+    controllers: Vec<GameController>,
+    hotkeys: Vec<Hotkey>,
+    rewinding: bool,
+}
+
+impl Input {
+    pub fn new(sdl_context: &sdl2::Sdl, keymap: &Keymap) -> Result<Self, String> {
+        let mut bindings = Vec::new();
+        let mut pad_bindings = Vec::new();
+        for key in 0..16 {
+            for name in keymap.bindings(key) {
+                if let Some(control) = name.strip_prefix("pad:") {
+                    match parse_pad_control(control) {
+                        Some(control) => pad_bindings.push((control, key)),
+                        None => return Err(format!("Unknown controller button '{}' in the keymap", control))
+                    }
+                    continue;
+                }
+                match Keycode::from_name(name) {
+                    Some(keycode) => bindings.push((keycode, key)),
+                    None => return Err(format!("Unknown key name '{}' in the keymap", name))
+                }
+            }
+        }
+        Ok(Input {
+            events: sdl_context.event_pump().unwrap(),
+            bindings,
+            pad_bindings,
+            controller_subsystem: sdl_context.game_controller().ok(),
+            controllers: Vec::new(),
+            hotkeys: Vec::new(),
+            rewinding: false
+        })
+    }
+
+    fn open_controller(&mut self, joystick_index: u32) {
+        if let Some(subsystem) = &self.controller_subsystem {
+            match subsystem.open(joystick_index) {
+                Ok(controller) => {
+                    eprintln!("Connected {}", controller.name());
+                    self.controllers.push(controller);
+                }
+                Err(e) => eprintln!("Failed to open controller {}: {}", joystick_index, e)
+            }
+        }
+    }
+
+    // true while the rewind key is held down
+    pub fn rewinding(&self) -> bool {
+        self.rewinding
+    }
+
+    // hotkeys pressed since the last call
+    pub fn take_hotkeys(&mut self) -> Vec<Hotkey> {
+        std::mem::take(&mut self.hotkeys)
+    }
+}
+
+impl Keypad for Input {
+    fn poll(&mut self) -> Result<[bool; 16], Quit> {
+
+        let events: Vec<Event> = self.events.poll_iter().collect();
+        for event in events {
+            match event {
+                Event::Quit { .. } => return Err(Quit),
+                // also sent for the controllers that are connected at startup
+                Event::ControllerDeviceAdded { which, .. } => self.open_controller(which),
+                Event::ControllerDeviceRemoved { which, .. } => self.controllers.retain(|controller| controller.instance_id() != which),
+                Event::KeyDown { keycode: Some(key), keymod, repeat: false, .. } => {
+                    let shift = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+                    if key == Keycode::F11 {
+                        self.hotkeys.push(Hotkey::ToggleGif);
+                    }
+                    if key == Keycode::F12 {
+                        self.hotkeys.push(Hotkey::Screenshot(if shift { ImageFormat::Pbm } else { ImageFormat::Png }));
+                    }
+                    if let Some(index) = SLOT_KEYS.iter().position(|k| *k == key) {
+                        let slot = index as u8 + 1;
+                        if shift {
+                            self.hotkeys.push(Hotkey::SaveState(slot));
+                        } else {
+                            self.hotkeys.push(Hotkey::LoadState(slot));
+                        }
+                    }
+                }
+                _ => ()
+            }
+        }
+
+        let keys: Vec<Keycode> = self.events
+            .keyboard_state()
+            .pressed_scancodes()
+            .filter_map(Keycode::from_scancode)
+            .collect();
+
+        let mut chip8_keys = [false; 16];
+        self.rewinding = keys.contains(&Keycode::Backspace);
+
+        for key in keys {
+            if key == Keycode::Escape {
+                return Err(Quit);
+            }
+            for (keycode, index) in &self.bindings {
+                if *keycode == key {
+                    chip8_keys[*index] = true;
+                }
+            }
+        }
+
+        for controller in &self.controllers {
+            for (control, index) in &self.pad_bindings {
+                let pressed = match control {
+                    PadControl::Button(button) => controller.button(*button),
+                    PadControl::Axis(axis, true) => controller.axis(*axis) > AXIS_THRESHOLD,
+                    PadControl::Axis(axis, false) => controller.axis(*axis) < -AXIS_THRESHOLD
+                };
+                if pressed {
+                    chip8_keys[*index] = true;
+                }
+            }
+        }
+
+        Ok(chip8_keys)
+    }
+}
+
+// a button name like dpup or an axis with a direction like leftx-
+fn parse_pad_control(name: &str) -> Option<PadControl> {
+    if let Some(axis) = name.strip_suffix('+') {
+        return Axis::from_string(axis).map(|axis| PadControl::Axis(axis, true));
+    }
+    if let Some(axis) = name.strip_suffix('-') {
+        return Axis::from_string(axis).map(|axis| PadControl::Axis(axis, false));
+    }
+    Button::from_string(name).map(PadControl::Button)
+}
\ No newline at end of file
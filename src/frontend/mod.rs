@@ -0,0 +1,16 @@
+mod display;
+mod input;
+mod audio;
+mod gif;
+mod renderer;
+// raw mode and non-blocking reads from stdin use termios
+#[cfg(all(unix, feature = "tui"))]
+mod terminal;
+
+pub use self::display::{palette, DisplayOptions, SdlDisplay, PALETTE_NAMES};
+pub use self::gif::GifRecorder;
+pub use self::renderer::{RenderMode, DEFAULT_DECAY, RENDER_MODE_NAMES};
+pub use self::input::{Hotkey, Input};
+#[cfg(all(unix, feature = "tui"))]
+pub use self::terminal::{TerminalDisplay, TerminalKeypad};
+pub use self::audio::{Audio, Beeper, NullAudio, DEFAULT_PITCH, DEFAULT_VOLUME, MAX_PITCH};
@@ -0,0 +1,179 @@
+use sdl2::pixels::Color;
+use chippie_ate::FrameBuffer;
+
+// how much of the previous frame is left in the phosphor mode by default
+pub const DEFAULT_DECAY: f32 = 0.6;
+pub const RENDER_MODE_NAMES: [&str; 3] = ["plain", "blend", "phosphor"];
+
+
+// games erase and redraw their sprites every frame, which makes them flicker. These modes only change what is
+// shown, the frame buffer the CPU sees stays the same
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RenderMode {
+    // every frame as it is
+    Plain,
+    // a pixel is lit when it was lit in this or the previous frame
+    Blend,
+    // lit pixels light up at once and fade out slowly, the decay is the part of the brightness kept every frame
+    Phosphor(f32),
+}
+
+impl RenderMode {
+    pub fn from_name(name: &str, decay: f32) -> Option<Self> {
+        match name {
+            "plain" => Some(RenderMode::Plain),
+            "blend" => Some(RenderMode::Blend),
+            "phosphor" => Some(RenderMode::Phosphor(decay)),
+            _ => None
+        }
+    }
+}
+
+// turns frame buffers into the colors that are shown, remembering what the earlier frames looked like
+pub struct Renderer {
+    mode: RenderMode,
+    previous: Vec<u8>,
+    shown: Vec<[f32; 3]>,
+    // the colors will still change without changes to the frame buffer
+    changing: bool,
+}
+
+impl Renderer {
+    pub fn new(mode: RenderMode) -> Self {
+        Renderer {
+            mode,
+            previous: Vec::new(),
+            shown: Vec::new(),
+            changing: false,
+        }
+    }
+
+    // false when rendering the same frame buffer again gives the same colors
+    pub fn changing(&self) -> bool {
+        self.changing
+    }
+
+    // the color of every pixel, row by row
+    pub fn colors(&mut self, frame_buffer: &FrameBuffer, palette: &[Color; 4]) -> Vec<Color> {
+        let mut pixels = Vec::with_capacity(frame_buffer.width() * frame_buffer.height());
+        for y in 0..frame_buffer.height() {
+            for x in 0..frame_buffer.width() {
+                pixels.push(frame_buffer.get(x, y));
+            }
+        }
+        match self.mode {
+            RenderMode::Plain => {
+                self.changing = false;
+                pixels.iter().map(|pixel| palette[*pixel as usize]).collect()
+            }
+            RenderMode::Blend => {
+                // start over when the resolution changed
+                if self.previous.len() != pixels.len() {
+                    self.previous = pixels.clone();
+                }
+                let colors = pixels.iter().zip(&self.previous).map(|(pixel, previous)| palette[(pixel | previous) as usize]).collect();
+                self.changing = self.previous != pixels;
+                self.previous = pixels;
+                colors
+            }
+            RenderMode::Phosphor(decay) => {
+                let targets: Vec<[f32; 3]> = pixels.iter().map(|pixel| {
+                    let color = palette[*pixel as usize];
+                    [color.r as f32, color.g as f32, color.b as f32]
+                }).collect();
+                if self.shown.len() != targets.len() {
+                    self.shown = targets.clone();
+                }
+                for ((shown, target), pixel) in self.shown.iter_mut().zip(&targets).zip(&pixels) {
+                    for channel in 0..3 {
+                        shown[channel] = if *pixel != 0 {
+                            target[channel]
+                        } else {
+                            shown[channel] * decay + target[channel] * (1.0 - decay)
+                        };
+                    }
+                }
+                let colors: Vec<Color> = self.shown.iter().map(|shown| Color::RGB(shown[0].round() as u8, shown[1].round() as u8, shown[2].round() as u8)).collect();
+                self.changing = colors.iter().zip(&pixels).any(|(color, pixel)| *color != palette[*pixel as usize]);
+                colors
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use sdl2::pixels::Color;
+    use chippie_ate::FrameBuffer;
+    use crate::frontend::renderer::{RenderMode, Renderer};
+
+    const PALETTE: [Color; 4] = [
+        Color::RGB(0, 0, 0),
+        Color::RGB(200, 100, 0),
+        Color::RGB(0, 0, 200),
+        Color::RGB(200, 200, 200)
+    ];
+
+    #[test]
+    fn test_plain() {
+        let mut frame_buffer = FrameBuffer::new();
+        let mut renderer = Renderer::new(RenderMode::Plain);
+        frame_buffer.draw_sprite(1, 0, &[0x80], false);
+        let colors = renderer.colors(&frame_buffer, &PALETTE);
+        assert_eq!(colors.len(), 64 * 32);
+        assert_eq!(colors[0], PALETTE[0]);
+        assert_eq!(colors[1], PALETTE[1]);
+        frame_buffer.clear();
+        assert_eq!(renderer.colors(&frame_buffer, &PALETTE)[1], PALETTE[0]);
+    }
+
+    #[test]
+    fn test_blend() {
+        let mut frame_buffer = FrameBuffer::new();
+        let mut renderer = Renderer::new(RenderMode::Blend);
+        frame_buffer.draw_sprite(0, 0, &[0x80], false);
+        assert_eq!(renderer.colors(&frame_buffer, &PALETTE)[0], PALETTE[1]);
+        // the sprite moved one pixel to the right
+        frame_buffer.clear();
+        frame_buffer.draw_sprite(1, 0, &[0x80], false);
+        let colors = renderer.colors(&frame_buffer, &PALETTE);
+        assert_eq!(colors[0], PALETTE[1]);
+        assert_eq!(colors[1], PALETTE[1]);
+        assert!(renderer.changing());
+        let colors = renderer.colors(&frame_buffer, &PALETTE);
+        assert_eq!(colors[0], PALETTE[0]);
+        assert!(!renderer.changing());
+    }
+
+    #[test]
+    fn test_phosphor() {
+        let mut frame_buffer = FrameBuffer::new();
+        let mut renderer = Renderer::new(RenderMode::Phosphor(0.5));
+        frame_buffer.draw_sprite(0, 0, &[0x80], false);
+        assert_eq!(renderer.colors(&frame_buffer, &PALETTE)[0], PALETTE[1]);
+        frame_buffer.clear();
+        assert_eq!(renderer.colors(&frame_buffer, &PALETTE)[0], Color::RGB(100, 50, 0));
+        assert_eq!(renderer.colors(&frame_buffer, &PALETTE)[0], Color::RGB(50, 25, 0));
+        assert!(renderer.changing());
+        for _ in 0..10 {
+            renderer.colors(&frame_buffer, &PALETTE);
+        }
+        assert!(!renderer.changing());
+        // lighting up again is immediate
+        frame_buffer.draw_sprite(0, 0, &[0x80], false);
+        assert_eq!(renderer.colors(&frame_buffer, &PALETTE)[0], PALETTE[1]);
+    }
+
+    #[test]
+    fn test_resolution_change() {
+        let mut frame_buffer = FrameBuffer::new();
+        let mut renderer = Renderer::new(RenderMode::Blend);
+        frame_buffer.draw_sprite(0, 0, &[0x80], false);
+        renderer.colors(&frame_buffer, &PALETTE);
+        frame_buffer.set_hires(true);
+        let colors = renderer.colors(&frame_buffer, &PALETTE);
+        assert_eq!(colors.len(), 128 * 64);
+        assert_eq!(colors[0], PALETTE[0]);
+    }
+}
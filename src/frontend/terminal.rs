@@ -0,0 +1,283 @@
+use std::io::{self, Write};
+use std::mem;
+use sdl2::pixels::Color;
+use chippie_ate::{Display, FrameBuffer, Keypad, Quit};
+use crate::frontend::display::DisplayOptions;
+use crate::frontend::renderer::Renderer;
+use crate::keymap::Keymap;
+
+// terminals only send key presses, a key counts as held this many frames after its last byte. Holding a key
+// down sends it again at the key repeat rate, but only after the initial delay of usually 250 to 600 ms, which
+// this has to cover so a held key isn't released and pressed again. Releasing a key takes as long to show
+const KEY_HOLD_FRAMES: u32 = 36;
+const CTRL_C: u8 = 0x03;
+const ESCAPE: u8 = 0x1B;
+
+
+// draws two rows of pixels per line of text with half blocks, the upper pixel in the foreground color and the
+// lower one in the background color
+pub struct TerminalDisplay {
+    v_ram: FrameBuffer,
+    palette: [Color; 4],
+    renderer: Renderer,
+    // the screen is cleared when the resolution changes
+    width: usize,
+}
+
+impl TerminalDisplay {
+    pub fn new(options: &DisplayOptions) -> Self {
+        // the screen is cleared on the first refresh
+        print!("\x1b[?25l");
+        TerminalDisplay {
+            v_ram: FrameBuffer::new(),
+            palette: options.palette,
+            renderer: Renderer::new(options.render_mode),
+            width: 0,
+        }
+    }
+}
+
+impl Display for TerminalDisplay {
+    fn v_ram(&mut self) -> &mut FrameBuffer {
+        &mut self.v_ram
+    }
+
+    fn clear_display(&mut self) {
+        self.v_ram.clear();
+    }
+
+    // the whole screen is written again when anything changed, with the cursor moved to the top left first
+    fn refresh(&mut self) {
+        if !self.v_ram.take_dirty() && !self.renderer.changing() {
+            return;
+        }
+        let mut text = String::new();
+        if self.width != self.v_ram.width() {
+            self.width = self.v_ram.width();
+            text.push_str("\x1b[2J");
+        }
+        text.push_str("\x1b[H");
+        let colors = self.renderer.colors(&self.v_ram, &self.palette);
+        text.push_str(&half_blocks(&colors, self.width));
+        let mut stdout = io::stdout().lock();
+        let _ = stdout.write_all(text.as_bytes());
+        let _ = stdout.flush();
+    }
+
+    fn framebuffer(&self) -> &FrameBuffer {
+        &self.v_ram
+    }
+}
+
+impl Drop for TerminalDisplay {
+    // leave the prompt below the screen in the normal colors
+    fn drop(&mut self) {
+        print!("\x1b[0m\x1b[?25h\r\n");
+        let _ = io::stdout().flush();
+    }
+}
+
+// one line per two rows of colors, the colors are only set again when they change
+fn half_blocks(colors: &[Color], width: usize) -> String {
+    let mut text = String::new();
+    for rows in colors.chunks(width * 2) {
+        let (upper, lower) = rows.split_at(width.min(rows.len()));
+        let mut current = None;
+        for (x, top) in upper.iter().enumerate() {
+            // an odd number of rows leaves the last line without a lower half
+            let bottom = lower.get(x).copied().unwrap_or(Color::RGB(0, 0, 0));
+            if current != Some((*top, bottom)) {
+                text.push_str(&format!("\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m", top.r, top.g, top.b, bottom.r, bottom.g, bottom.b));
+                current = Some((*top, bottom));
+            }
+            text.push('▀');
+        }
+        text.push_str("\x1b[0m\r\n");
+    }
+    text
+}
+
+
+// reads the keys from stdin in raw mode, with the bindings of the keymap that can be typed: single characters,
+// Space, Return and the arrow keys. Escape and Ctrl+C quit
+pub struct TerminalKeypad {
+    bindings: Vec<(String, usize)>,
+    // frames left until each key is released
+    held: [u32; 16],
+    // the terminal settings to go back to
+    original: Option<libc::termios>,
+}
+
+impl TerminalKeypad {
+    pub fn new(keymap: &Keymap) -> Self {
+        let mut bindings = Vec::new();
+        for key in 0..16 {
+            for name in keymap.bindings(key) {
+                bindings.push((name.to_lowercase(), key));
+            }
+        }
+        TerminalKeypad {
+            bindings,
+            held: [0; 16],
+            original: None,
+        }
+    }
+
+    // without echo and line buffering, reads return at once even when nothing was typed
+    pub fn enter_raw_mode(&mut self) -> Result<(), String> {
+        unsafe {
+            let mut original: libc::termios = mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut original) != 0 {
+                return Err("--tui needs a terminal".to_string());
+            }
+            let mut raw = original;
+            libc::cfmakeraw(&mut raw);
+            // keep turning \n into \r\n, so error messages still look right
+            raw.c_oflag = original.c_oflag;
+            raw.c_cc[libc::VMIN] = 0;
+            raw.c_cc[libc::VTIME] = 0;
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) != 0 {
+                return Err(format!("Failed to set up the terminal: {}", io::Error::last_os_error()));
+            }
+            self.original = Some(original);
+        }
+        Ok(())
+    }
+
+    // the keys held after reading the bytes of one frame, an error when the user wants to quit
+    fn handle_input(&mut self, bytes: &[u8]) -> Result<[bool; 16], Quit> {
+        for frames in self.held.iter_mut() {
+            *frames = frames.saturating_sub(1);
+        }
+        for name in key_names(bytes) {
+            if name == "escape" || name == "ctrl+c" {
+                return Err(Quit);
+            }
+            for (binding, key) in &self.bindings {
+                if *binding == name {
+                    self.held[*key] = KEY_HOLD_FRAMES;
+                }
+            }
+        }
+        Ok(self.held.map(|frames| frames > 0))
+    }
+}
+
+impl Keypad for TerminalKeypad {
+    fn poll(&mut self) -> Result<[bool; 16], Quit> {
+        let mut bytes = Vec::new();
+        let mut buffer = [0u8; 64];
+        loop {
+            let count = unsafe { libc::read(libc::STDIN_FILENO, buffer.as_mut_ptr() as *mut libc::c_void, buffer.len()) };
+            if count <= 0 {
+                break;
+            }
+            bytes.extend_from_slice(&buffer[..count as usize]);
+        }
+        self.handle_input(&bytes)
+    }
+}
+
+impl Drop for TerminalKeypad {
+    fn drop(&mut self) {
+        if let Some(original) = self.original.take() {
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &original);
+            }
+        }
+    }
+}
+
+// the lowercase SDL names of the keys that were typed, escape sequences of other keys are skipped
+fn key_names(bytes: &[u8]) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut position = 0;
+    while position < bytes.len() {
+        let byte = bytes[position];
+        position += 1;
+        match byte {
+            CTRL_C => names.push("ctrl+c".to_string()),
+            // arrows are sent as ESC [ A or ESC O A, a lone escape is the escape key
+            ESCAPE if position == bytes.len() => names.push("escape".to_string()),
+            ESCAPE => {
+                if bytes[position] == b'[' || bytes[position] == b'O' {
+                    position += 1;
+                }
+                // parameters come before the final letter of the sequence
+                while position < bytes.len() && !bytes[position].is_ascii_alphabetic() && bytes[position] != b'~' {
+                    position += 1;
+                }
+                let name = match bytes.get(position) {
+                    Some(b'A') => "up",
+                    Some(b'B') => "down",
+                    Some(b'C') => "right",
+                    Some(b'D') => "left",
+                    _ => ""
+                };
+                if !name.is_empty() {
+                    names.push(name.to_string());
+                }
+                position += 1;
+            }
+            b' ' => names.push("space".to_string()),
+            b'\r' | b'\n' => names.push("return".to_string()),
+            _ if byte.is_ascii_graphic() => names.push((byte as char).to_ascii_lowercase().to_string()),
+            _ => ()
+        }
+    }
+    names
+}
+
+
+#[cfg(test)]
+mod tests {
+    use sdl2::pixels::Color;
+    use crate::frontend::terminal::{half_blocks, key_names, TerminalKeypad, KEY_HOLD_FRAMES};
+    use crate::keymap::Keymap;
+
+    const BLACK: Color = Color::RGB(0, 0, 0);
+    const GREEN: Color = Color::RGB(0, 250, 0);
+
+    #[test]
+    fn test_half_blocks() {
+        // a 2x2 screen with the top left and bottom right pixels lit
+        let text = half_blocks(&[GREEN, BLACK, BLACK, GREEN], 2);
+        assert_eq!(text, "\x1b[38;2;0;250;0m\x1b[48;2;0;0;0m▀\x1b[38;2;0;0;0m\x1b[48;2;0;250;0m▀\x1b[0m\r\n");
+
+        // the colors are set once for a run of equal pixels
+        let text = half_blocks(&[BLACK; 8], 4);
+        assert_eq!(text, "\x1b[38;2;0;0;0m\x1b[48;2;0;0;0m▀▀▀▀\x1b[0m\r\n");
+
+        // 64x32 pixels take 16 lines of 64 characters
+        let text = half_blocks(&[BLACK; 64 * 32], 64);
+        assert_eq!(text.lines().count(), 16);
+        assert_eq!(text.chars().filter(|c| *c == '▀').count(), 64 * 16);
+    }
+
+    #[test]
+    fn test_key_names() {
+        assert_eq!(key_names(b"wQ 1\r"), ["w", "q", "space", "1", "return"]);
+        assert_eq!(key_names(b"\x1b[A\x1bOD\x1b[1;2Cx"), ["up", "left", "right", "x"]);
+        assert_eq!(key_names(b"\x1b[15~a"), ["a"]);
+        assert_eq!(key_names(b"\x1b"), ["escape"]);
+        assert_eq!(key_names(b"\x03"), ["ctrl+c"]);
+    }
+
+    #[test]
+    fn test_held_keys() {
+        let mut keypad = TerminalKeypad::new(&Keymap::default());
+        let keys = keypad.handle_input(b"w").unwrap();
+        assert!(keys[0x5]);
+        assert_eq!(keys.iter().filter(|key| **key).count(), 1);
+        // released a few frames after the last byte
+        for _ in 1..KEY_HOLD_FRAMES {
+            assert!(keypad.handle_input(b"").unwrap()[0x5]);
+        }
+        assert!(!keypad.handle_input(b"").unwrap()[0x5]);
+        // bindings are not case sensitive
+        assert!(keypad.handle_input(b"X").unwrap()[0x0]);
+
+        assert!(keypad.handle_input(b"\x1b").is_err());
+        assert!(keypad.handle_input(b"a\x03").is_err());
+    }
+}
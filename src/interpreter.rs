@@ -27,7 +27,7 @@ impl<'a> Interpreter<'a> {
     pub fn interpret_line(&mut self, line: &str) -> Result<bool, String> {
         // interpret a line and return an optional issue
         // ignore empty lines
-        if line.eq("") {
+        if line.is_empty() {
             return Ok(true);
         }
         // ignore comment lines
@@ -35,7 +35,7 @@ impl<'a> Interpreter<'a> {
             return Ok(true);
         }
         let values: Vec<&str> = line.split(" ").collect();
-        let command = match values.get(0) {
+        let command = match values.first() {
             Some(c) => *c,
             None => return Ok(true)
         };
@@ -86,14 +86,14 @@ impl<'a> Interpreter<'a> {
     fn read_special_command(&mut self, command: &str, values: &Vec<&str>) -> Result<bool, String> {
         match command {
             "#f" => {
-                if self.current_function != None {
+                if self.current_function.is_some() {
                     return Err("Cannot start a function in another function".to_string());
                 }
                 let name = match values.get(1) {
                     Some(v) => *v,
                     None => return Err("You need to provide a name when defining a function".to_string())
                 };
-                if self.definition_map.get(name).is_some() {
+                if self.definition_map.contains_key(name) {
                     return Err(format!("Redeclared function name {}", name));
                 }
                 self.definition_map.insert(name.to_string(), Vec::new());
@@ -123,15 +123,13 @@ impl<'a> Interpreter<'a> {
     }
 
     fn set_reference(&mut self, command: &str, _values: &Vec<&str>) -> Result<bool, String> {
-        if !self.definition_map.get(command).is_some() {
+        if !self.definition_map.contains_key(command) {
             return Err(format!("Invalid instruction {}", command));
         }
         match self.references.get_mut(command) {
             Some(vec) => vec.push(self.offset),
             None => {
-                let mut vec = Vec::new();
-                vec.push(self.offset);
-                self.references.insert(command.to_string(), vec);
+                self.references.insert(command.to_string(), vec![self.offset]);
             }
         };
         // placeholder --> invalid instruction
@@ -224,9 +222,7 @@ mod tests {
     fn test_add_x_instruction() {
         let mut ram = RAM::new();
         let mut interpreter = Interpreter::new(&mut ram);
-        let mut values: Vec<&str> = Vec::new();
-        values.push("0");
-        values.push("A");
+        let values: Vec<&str> = vec!["0", "A"];
         assert_eq!(interpreter.add_x_instruction(0xF, &values, 0x29), Ok(true));
         assert_eq!(interpreter.offset, RAM_OFFSET + 2);
         assert_eq!(ram.get(RAM_OFFSET), 0xFA);
@@ -237,9 +233,7 @@ mod tests {
     fn get_u16_value() {
         let mut ram = RAM::new();
         let interpreter = Interpreter::new(&mut ram);
-        let mut values: Vec<&str> = Vec::new();
-        values.push("0");
-        values.push("A");
+        let values: Vec<&str> = vec!["0", "A"];
         let result = interpreter.get_u16_value(&values, 1);
         assert_eq!(result, Ok(0xA));
     }
@@ -248,8 +242,7 @@ mod tests {
     fn get_u16_value_fail1() {
         let mut ram = RAM::new();
         let interpreter = Interpreter::new(&mut ram);
-        let mut values: Vec<&str> = Vec::new();
-        values.push("0");
+        let values: Vec<&str> = vec!["0"];
         assert_eq!(interpreter.get_u16_value(&values, 1).expect_err(""), "Invalid index '1'");
     }
 
@@ -257,9 +250,7 @@ mod tests {
     fn get_u16_value_fail2() {
         let mut ram = RAM::new();
         let interpreter = Interpreter::new(&mut ram);
-        let mut values: Vec<&str> = Vec::new();
-        values.push("0");
-        values.push("G");
+        let values: Vec<&str> = vec!["0", "G"];
         assert_eq!(interpreter.get_u16_value(&values, 1).expect_err(""), "Invalid u16 'G'");
     }
 
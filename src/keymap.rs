@@ -0,0 +1,211 @@
+use std::fs;
+
+
+// the COSMAC VIP keypad on the left side of a QWERTY keyboard
+//   1 2 3 C      1 2 3 4
+//   4 5 6 D  ->  Q W E R
+//   7 8 9 E      A S D F
+//   A 0 B F      Z X C V
+const DEFAULT_KEYS: [&str; 16] = [
+    "X", "1", "2", "3",
+    "Q", "W", "E", "A",
+    "S", "D", "Z", "C",
+    "4", "R", "F", "V"
+];
+// controls of a game controller start with pad: followed by the SDL button name, or the axis name and the
+// direction. The D-pad and left stick work like WASD in Octo, with A and B as E and Q
+const DEFAULT_PAD_BINDINGS: [(usize, &str); 10] = [
+    (0x5, "pad:dpup"), (0x7, "pad:dpleft"), (0x8, "pad:dpdown"), (0x9, "pad:dpright"),
+    (0x5, "pad:lefty-"), (0x7, "pad:leftx-"), (0x8, "pad:lefty+"), (0x9, "pad:leftx+"),
+    (0x6, "pad:a"), (0x4, "pad:b")
+];
+
+// the part of the file a binding is in
+enum Section {
+    Default,
+    Rom,
+    OtherRom,
+}
+
+// SDL key and controller names bound to each of the 16 keys, a key can have any number of bindings
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keymap {
+    bindings: [Vec<String>; 16],
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut bindings = DEFAULT_KEYS.map(|name| vec![name.to_string()]);
+        for (key, name) in DEFAULT_PAD_BINDINGS {
+            bindings[key].push(name.to_string());
+        }
+        Keymap { bindings }
+    }
+}
+
+impl Keymap {
+    // a subset of TOML, keys that are not mentioned keep their default bindings
+    //   [default]
+    //   5 = ["W", "Up"]
+    //   [roms."pong.ch8"]
+    //   1 = ["Up", "pad:dpup"]
+    //   4 = ["Down", "pad:lefty+"]
+    //   C = []
+    // the section of the ROM is applied on top of the default section. The list replaces every binding of the key,
+    // including the default controller bindings, so 5 = ["W", "Up"] leaves the key without pad:dpup
+    pub fn parse(config: &str, rom: &str) -> Result<Self, String> {
+        let mut keymap = Keymap::default();
+        let mut rom_bindings = Vec::new();
+        let mut section = None;
+        for (number, line) in config.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: &str| format!("{} at line {} of the keymap: {}", message, number + 1, line);
+            if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+                section = match name.trim() {
+                    "default" => Some(Section::Default),
+                    name => match name.strip_prefix("roms.").and_then(unquote) {
+                        Some(name) if name == rom => Some(Section::Rom),
+                        Some(_) => Some(Section::OtherRom),
+                        None => return Err(error("Invalid section"))
+                    }
+                };
+                continue;
+            }
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return Err(error("Expected key = bindings"))
+            };
+            let key = match u8::from_str_radix(key, 16) {
+                Ok(index) if key.len() == 1 => index as usize,
+                _ => return Err(error("Invalid key"))
+            };
+            let names = parse_names(value).ok_or_else(|| error("Invalid bindings"))?;
+            match section {
+                Some(Section::Default) => keymap.bindings[key] = names,
+                Some(Section::Rom) => rom_bindings.push((key, names)),
+                Some(Section::OtherRom) => (),
+                None => return Err(error("Bindings outside of a section"))
+            }
+        }
+        // the ROM section wins wherever it is in the file
+        for (key, names) in rom_bindings {
+            keymap.bindings[key] = names;
+        }
+        Ok(keymap)
+    }
+
+    pub fn read(filename: &str, rom: &str) -> Result<Self, String> {
+        match fs::read_to_string(filename) {
+            Ok(config) => Keymap::parse(&config, rom),
+            Err(e) => Err(format!("Failed to read {}: {}", filename, e))
+        }
+    }
+
+    // the key names bound to a CHIP-8 key
+    pub fn bindings(&self, key: usize) -> &[String] {
+        &self.bindings[key]
+    }
+}
+
+// "name" or ["name", "other name"]
+fn parse_names(value: &str) -> Option<Vec<String>> {
+    match value.strip_prefix('[').and_then(|value| value.strip_suffix(']')) {
+        Some(list) if list.trim().is_empty() => Some(Vec::new()),
+        Some(list) => split_list(list).into_iter().map(|name| unquote(name.trim())).collect(),
+        None => unquote(value).map(|name| vec![name])
+    }
+}
+
+// split on the commas between the names, but not on a quoted "," key
+fn split_list(list: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    for (index, c) in list.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                names.push(&list[start..index]);
+                start = index + 1;
+            }
+            _ => ()
+        }
+    }
+    names.push(&list[start..]);
+    names
+}
+
+fn unquote(value: &str) -> Option<String> {
+    value.strip_prefix('"').and_then(|value| value.strip_suffix('"')).map(|value| value.to_string())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::keymap::Keymap;
+
+    const CONFIG: &str = r#"
+# arrows for everything
+[default]
+5 = ["W", "Up"]
+8 = ["S", "Down"]
+
+[roms."pong.ch8"]
+1 = ["Up", "pad:dpup"]
+4 = "Down"
+5 = []
+
+[roms."tetris.ch8"]
+A = "Space"
+"#;
+
+    #[test]
+    fn test_default_keymap() {
+        let keymap = Keymap::default();
+        assert_eq!(keymap.bindings(0x0), ["X"]);
+        assert_eq!(keymap.bindings(0xC), ["4"]);
+        assert_eq!(keymap.bindings(0xF), ["V"]);
+        assert_eq!(keymap.bindings(0x5), ["W", "pad:dpup", "pad:lefty-"]);
+        assert_eq!(keymap.bindings(0x6), ["E", "pad:a"]);
+    }
+
+    #[test]
+    fn test_parse_keymap() {
+        let keymap = Keymap::parse(CONFIG, "breakout.ch8").unwrap();
+        // the controller bindings of the key are replaced as well
+        assert_eq!(keymap.bindings(0x5), ["W", "Up"]);
+        assert_eq!(keymap.bindings(0x8), ["S", "Down"]);
+        assert_eq!(keymap.bindings(0x1), ["1"]);
+        assert_eq!(keymap.bindings(0xA), ["Z"]);
+    }
+
+    #[test]
+    fn test_parse_rom_keymap() {
+        let keymap = Keymap::parse(CONFIG, "pong.ch8").unwrap();
+        assert_eq!(keymap.bindings(0x1), ["Up", "pad:dpup"]);
+        assert_eq!(keymap.bindings(0x4), ["Down"]);
+        assert!(keymap.bindings(0x5).is_empty());
+        assert_eq!(keymap.bindings(0x8), ["S", "Down"]);
+        assert_eq!(keymap.bindings(0xA), ["Z"]);
+    }
+
+    #[test]
+    fn test_parse_punctuation() {
+        let keymap = Keymap::parse("[default]\nB = [\",\", \"=\"]\nC = \",\"\n", "").unwrap();
+        assert_eq!(keymap.bindings(0xB), [",", "="]);
+        assert_eq!(keymap.bindings(0xC), [","]);
+        assert!(Keymap::parse("[default]\nB = [\",\", \"]", "").is_err());
+    }
+
+    #[test]
+    fn test_parse_invalid_keymap() {
+        assert_eq!(Keymap::parse("5 = \"W\"", "").expect_err(""), "Bindings outside of a section at line 1 of the keymap: 5 = \"W\"");
+        assert_eq!(Keymap::parse("[default]\nG = \"W\"", "").expect_err(""), "Invalid key at line 2 of the keymap: G = \"W\"");
+        assert_eq!(Keymap::parse("[default]\n10 = \"W\"", "").expect_err(""), "Invalid key at line 2 of the keymap: 10 = \"W\"");
+        assert_eq!(Keymap::parse("[default]\n5 = W", "").expect_err(""), "Invalid bindings at line 2 of the keymap: 5 = W");
+        assert_eq!(Keymap::parse("[keys]", "").expect_err(""), "Invalid section at line 1 of the keymap: [keys]");
+    }
+}
@@ -0,0 +1,33 @@
+#![allow(clippy::upper_case_acronyms)]
+
+// a CHIP-8, SUPER-CHIP and XO-CHIP emulator. Machine bundles everything needed to run a program:
+//   let mut machine = Machine::new(CPU::new(), RAM::new(), HeadlessDisplay::new(), 700);
+//   machine.load_program("pong.ch8")?;
+//   machine.set_keys(keys);
+//   machine.run_frame()?;
+// or polls a Keypad for the keys, which gives Err(Quit) instead of running the frame when it is done:
+//   machine.run_frame_with(&mut keypad)
+// Interpreter assembles the text programs of this project into memory
+// a front end implements Display to show the screen and Keypad to read the keys, the window and the terminal
+// of the chippie_ate binary are two of them
+
+extern crate rand;
+extern crate rand_chacha;
+
+mod cpu;
+mod ram;
+mod drivers;
+mod interpreter;
+mod machine;
+mod quirks;
+mod save_state;
+mod scheduler;
+
+pub use cpu::{CpuError, StepOutcome, CPU};
+pub use drivers::{Cartridge, Display, FrameBuffer, HeadlessDisplay, Keypad, Quit, ScriptedKeypad, HIRES_HEIGHT, HIRES_WIDTH, LORES_HEIGHT, LORES_WIDTH};
+pub use drivers::{encode_screenshot, next_filename, save_screenshot, ImageFormat};
+pub use interpreter::Interpreter;
+pub use machine::Machine;
+pub use quirks::Quirks;
+pub use ram::{RAM, XO_RAM_SIZE};
+pub use scheduler::{DEFAULT_CLOCK_SPEED, FRAME_RATE};
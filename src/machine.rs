@@ -0,0 +1,209 @@
+use crate::cpu::{CpuError, StepOutcome, CPU};
+use crate::drivers::{Cartridge, Display, FrameBuffer, Keypad, Quit};
+use crate::ram::RAM;
+use crate::save_state;
+use crate::scheduler::Scheduler;
+
+
+// a complete CHIP-8 computer: the CPU with its memory, a display to draw on and the 16 keys of the keypad.
+// It runs as fast as it is asked to, front ends that run in real time wait for the next frame in between
+pub struct Machine<D: Display> {
+    cpu: CPU,
+    ram: RAM,
+    display: D,
+    keys: [bool; 16],
+    // spreads the clock speed over the frames and paces them
+    scheduler: Scheduler,
+}
+
+impl<D: Display> Machine<D> {
+    pub fn new(cpu: CPU, ram: RAM, display: D, clock_speed: u32) -> Self {
+        Machine {
+            cpu,
+            ram,
+            display,
+            keys: [false; 16],
+            scheduler: Scheduler::new(clock_speed),
+        }
+    }
+
+    // a binary ROM or an assembly listing, depending on the file
+    pub fn load_program(&mut self, filename: &str) -> Result<(), String> {
+        Cartridge::read(filename, &mut self.ram)
+    }
+
+    pub fn load_binary(&mut self, content: &[u8]) -> Result<(), String> {
+        Cartridge::load_binary(content, &mut self.ram)
+    }
+
+    // the keys that are held down from now on, indexed by the hex value of the key
+    pub fn set_keys(&mut self, keys: [bool; 16]) {
+        self.keys = keys;
+    }
+
+    // execute a single instruction
+    pub fn step(&mut self) -> Result<StepOutcome, CpuError> {
+        self.cpu.tick(&mut self.ram, Some(&self.keys), Some(&mut self.display))
+    }
+
+    // one 60th of a second: the instructions for the clock speed, then the timers count down and the display
+    // is refreshed. Stops early when the program exits
+    pub fn run_frame(&mut self) -> Result<StepOutcome, CpuError> {
+        for _ in 0..self.scheduler.instructions_for_frame() {
+            if self.step()? == StepOutcome::Exit {
+                return Ok(StepOutcome::Exit);
+            }
+        }
+        self.cpu.decrement_timers();
+        self.display.refresh();
+        Ok(StepOutcome::Continue)
+    }
+
+    // a frame with the keys the keypad gives for it. Quit is passed on, the frame is not run then
+    pub fn run_frame_with<K: Keypad + ?Sized>(&mut self, keypad: &mut K) -> Result<Result<StepOutcome, CpuError>, Quit> {
+        self.set_keys(keypad.poll()?);
+        Ok(self.run_frame())
+    }
+
+    // sleep until the next 60th of a second, so frames are run in real time
+    pub fn wait_for_next_frame(&mut self) {
+        self.scheduler.wait_for_next_frame();
+    }
+
+    // what the program has drawn so far
+    pub fn framebuffer(&self) -> &FrameBuffer {
+        self.display.framebuffer()
+    }
+
+    pub fn save_state(&self) -> Vec<u8> {
+        save_state::save(&self.cpu, &self.ram, self.display.framebuffer())
+    }
+
+    // nothing is changed when the state is invalid
+    pub fn load_state(&mut self, bytes: &[u8]) -> Result<(), String> {
+        save_state::load(bytes, &mut self.cpu, &mut self.ram, self.display.v_ram())
+    }
+
+    pub fn save_to_file(&self, path: &str) -> Result<(), String> {
+        save_state::save_to_file(path, &self.cpu, &self.ram, self.display.framebuffer())
+    }
+
+    pub fn load_from_file(&mut self, path: &str) -> Result<(), String> {
+        save_state::load_from_file(path, &mut self.cpu, &mut self.ram, self.display.v_ram())
+    }
+
+    pub fn cpu(&self) -> &CPU {
+        &self.cpu
+    }
+
+    pub fn cpu_mut(&mut self) -> &mut CPU {
+        &mut self.cpu
+    }
+
+    pub fn ram(&self) -> &RAM {
+        &self.ram
+    }
+
+    pub fn ram_mut(&mut self) -> &mut RAM {
+        &mut self.ram
+    }
+
+    pub fn display(&self) -> &D {
+        &self.display
+    }
+
+    pub fn display_mut(&mut self) -> &mut D {
+        &mut self.display
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::{StepOutcome, CPU};
+    use crate::drivers::{HeadlessDisplay, Keypad, Quit};
+    use crate::machine::Machine;
+    use crate::ram::RAM;
+
+    fn with_program(program: &[u8], clock_speed: u32) -> Machine<HeadlessDisplay> {
+        let mut machine = Machine::new(CPU::new(), RAM::new(), HeadlessDisplay::new(), clock_speed);
+        machine.load_binary(program).unwrap();
+        machine
+    }
+
+    #[test]
+    fn test_step() {
+        // V0 = 5, V1 = 7
+        let mut machine = with_program(&[0x60, 0x05, 0x61, 0x07], 600);
+        assert_eq!(machine.step(), Ok(StepOutcome::Continue));
+        assert_eq!(machine.cpu().read_register(0), 5);
+        assert_eq!(machine.cpu().read_register(1), 0);
+        assert_eq!(machine.step(), Ok(StepOutcome::Continue));
+        assert_eq!(machine.cpu().read_register(1), 7);
+    }
+
+    #[test]
+    fn test_run_frame() {
+        // draw the 0 of the font at 0,0, set the delay timer and loop forever
+        let program = [0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05, 0x62, 0x0A, 0xF2, 0x15, 0x12, 0x0A];
+        let mut machine = with_program(&program, 600);
+        assert_eq!(machine.run_frame(), Ok(StepOutcome::Continue));
+        assert_eq!(machine.framebuffer().get(0, 0), 1);
+        assert_eq!(machine.framebuffer().get(4, 0), 0);
+        assert_eq!(machine.cpu().delay_timer(), 9);
+        assert_eq!(machine.display().refreshes(), 1);
+    }
+
+    #[test]
+    fn test_set_keys() {
+        // V1 = 1 while key 5 is held: skip the jump to the end when it is
+        let program = [0x60, 0x05, 0xE0, 0x9E, 0x12, 0x08, 0x61, 0x01, 0x12, 0x08];
+        let mut machine = with_program(&program, 600);
+        machine.run_frame().unwrap();
+        assert_eq!(machine.cpu().read_register(1), 0);
+
+        let mut machine = with_program(&program, 600);
+        let mut keys = [false; 16];
+        keys[5] = true;
+        machine.set_keys(keys);
+        machine.run_frame().unwrap();
+        assert_eq!(machine.cpu().read_register(1), 1);
+    }
+
+    // holds key 5 for a number of frames, then wants to quit
+    struct CountdownKeypad(u32);
+
+    impl Keypad for CountdownKeypad {
+        fn poll(&mut self) -> Result<[bool; 16], Quit> {
+            self.0 = self.0.checked_sub(1).ok_or(Quit)?;
+            let mut keys = [false; 16];
+            keys[5] = true;
+            Ok(keys)
+        }
+    }
+
+    #[test]
+    fn test_run_frame_with() {
+        // V1 += 1 every frame key 5 is held
+        let program = [0x60, 0x05, 0xE0, 0xAE, 0x71, 0x01, 0x12, 0x02];
+        let mut machine = with_program(&program, 180);
+        let mut keypad = CountdownKeypad(2);
+        assert_eq!(machine.run_frame_with(&mut keypad), Ok(Ok(StepOutcome::Continue)));
+        assert_eq!(machine.run_frame_with(&mut keypad), Ok(Ok(StepOutcome::Continue)));
+        assert_eq!(machine.run_frame_with(&mut keypad), Err(Quit));
+        assert_eq!(machine.display().refreshes(), 2);
+        assert_eq!(machine.cpu().read_register(1), 2);
+    }
+
+    #[test]
+    fn test_save_state() {
+        let mut machine = with_program(&[0x60, 0x05, 0x60, 0x09], 600);
+        machine.step().unwrap();
+        let state = machine.save_state();
+        machine.step().unwrap();
+        assert_eq!(machine.cpu().read_register(0), 9);
+        machine.load_state(&state).unwrap();
+        assert_eq!(machine.cpu().read_register(0), 5);
+        assert!(machine.load_state(&[1, 2, 3]).is_err());
+    }
+}
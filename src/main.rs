@@ -1,36 +1,332 @@
+extern crate chippie_ate;
 extern crate sdl2;
-extern crate rand;
+#[cfg(all(unix, feature = "tui"))]
+extern crate libc;
 
-mod cpu;
-mod ram;
-mod drivers;
-mod interpreter;
+mod frontend;
+mod keymap;
+mod movie;
+mod options;
+mod rewind;
 
 use std::env;
-use cpu::CPU;
-use drivers::{Display, Input};
-use ram::RAM;
-use crate::drivers::Cartridge;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::process;
+use chippie_ate::{next_filename, Cartridge, Display, HeadlessDisplay, ImageFormat, Keypad, Machine, Quit, ScriptedKeypad, StepOutcome, CPU, FRAME_RATE, RAM, XO_RAM_SIZE};
+use frontend::{Audio, GifRecorder, Beeper, Hotkey, Input, NullAudio, SdlDisplay};
+#[cfg(all(unix, feature = "tui"))]
+use frontend::{TerminalDisplay, TerminalKeypad};
+use keymap::Keymap;
+use movie::{Movie, MoviePlayer, Settings};
+use options::Options;
+use rewind::Rewind;
 
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let program = match args.get(1){
-        Some(program) => program,
-        None => panic!("No program file provided!")
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match Options::parse(&args) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(1);
+        }
+    };
+    let mut cpu = CPU::new();
+    cpu.set_quirks(options.quirks);
+    if let Some(seed) = options.seed {
+        cpu.set_seed(seed);
+    }
+    let mut ram = if options.xo_chip { RAM::with_size(XO_RAM_SIZE) } else { RAM::new() };
+    if let Err(message) = Cartridge::read(&options.program, &mut ram) {
+        eprintln!("{}", message);
+        process::exit(1);
+    }
+    // a movie only replays correctly with the random numbers and the settings it was recorded with
+    let settings = Settings { clock_speed: options.clock_speed, quirks: options.quirks, xo_chip: options.xo_chip };
+    let movie = options.play.as_ref().map(|filename| Movie::read(filename).and_then(|movie| {
+        movie.check_settings(&settings)?;
+        Ok(movie)
+    }));
+    let mut player = movie.map(|movie| match movie {
+        Ok(movie) => {
+            cpu.set_seed(movie.seed());
+            MoviePlayer::new(movie)
+        }
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(1);
+        }
+    });
+    if let Some(frames) = options.headless_frames {
+        let mut keypad: Box<dyn Keypad> = match (player, &options.key_script) {
+            (Some(player), _) => Box::new(player),
+            (None, Some(filename)) => match ScriptedKeypad::read(filename) {
+                Ok(keypad) => Box::new(keypad),
+                Err(message) => {
+                    eprintln!("{}", message);
+                    process::exit(1);
+                }
+            },
+            (None, None) => Box::new(ScriptedKeypad::new())
+        };
+        let mut machine = Machine::new(cpu, ram, HeadlessDisplay::new(), options.clock_speed);
+        run_headless(&mut machine, keypad.as_mut(), &options, frames);
+        return;
+    }
+    // the keymap can have a section for the file name of the ROM
+    let rom = Path::new(&options.program).file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let keymap = match &options.keymap {
+        Some(filename) => Keymap::read(filename, &rom),
+        None => Ok(Keymap::default())
+    };
+    let keymap = match keymap {
+        Ok(keymap) => keymap,
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(1);
+        }
     };
+    // options only allow --tui when the terminal front end is built
+    #[cfg(all(unix, feature = "tui"))]
+    if options.tui {
+        // the terminal has to be restored before anything is printed
+        if let Err(message) = run_terminal(cpu, ram, player, settings, &keymap, &options) {
+            eprintln!("{}", message);
+            process::exit(1);
+        }
+        return;
+    }
+    let mut recording = options.record.as_ref().map(|_| Movie::new(cpu.seed(), settings));
+
     let sdl_context = sdl2::init().unwrap();
-    let mut display = Display::new(&sdl_context);
-    let mut input = Input::new(&sdl_context);
+    let mut display_options = options.display.clone();
+    display_options.title = format!("{} - {}", display_options.title, options.program);
+    let display = SdlDisplay::new(&sdl_context, &display_options);
+    let mut input = match Input::new(&sdl_context, &keymap) {
+        Ok(input) => input,
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(1);
+        }
+    };
+    // fall back to silence on machines without an audio device
+    let mut audio: Box<dyn Beeper> = match Audio::new(&sdl_context, options.pitch, options.volume) {
+        Ok(audio) => Box::new(audio),
+        Err(_) => Box::new(NullAudio {})
+    };
 
-    let mut cpu = CPU::new();
-    let mut ram = RAM::new();
-    Cartridge::read(program, &mut ram);
+    let mut machine = Machine::new(cpu, ram, display, options.clock_speed);
+    let mut rewind = Rewind::new((options.rewind_seconds * FRAME_RATE) as usize);
+    let mut gif = options.gif.as_ref().map(|filename| match GifRecorder::create(filename, &options.display.palette) {
+        Ok(recorder) => recorder,
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(1);
+        }
+    });
+    let mut exit_code = 0;
+    while let Ok(live_keys) = input.poll(){
+        for hotkey in input.take_hotkeys() {
+            let result = match hotkey {
+                Hotkey::SaveState(slot) => machine.save_to_file(&state_path(&options.program, slot)),
+                // a movie can't jump to another state
+                Hotkey::LoadState(_) if recording.is_some() || player.is_some() => {
+                    Err("Loading a state is not possible while a movie is recorded or played".to_string())
+                }
+                Hotkey::LoadState(slot) => machine.load_from_file(&state_path(&options.program, slot)),
+                Hotkey::Screenshot(format) => {
+                    // numbered files next to the ROM, pong-001.png, pong-002.png, ...
+                    let prefix = Path::new(&options.program).with_extension("").to_string_lossy().to_string();
+                    let filename = next_filename(&prefix, format.extension());
+                    let palette = options.display.palette.map(|color| color.rgb());
+                    machine.display().save_screenshot(&filename, format, &palette, options.display.scale).map(|_| eprintln!("Saved {}", filename))
+                }
+                Hotkey::ToggleGif => match gif.take() {
+                    Some(recorder) => finish_gif(recorder),
+                    None => {
+                        let prefix = Path::new(&options.program).with_extension("").to_string_lossy().to_string();
+                        let filename = next_filename(&prefix, "gif");
+                        GifRecorder::create(&filename, &options.display.palette).map(|recorder| {
+                            eprintln!("Recording {}", filename);
+                            gif = Some(recorder);
+                        })
+                    }
+                }
+            };
+            if let Err(message) = result {
+                eprintln!("{}", message);
+            }
+        }
+        // rewinding is undone in a recording, but a movie that is played back can't go back in time
+        if input.rewinding() && player.is_none() {
+            // go back one frame per frame, the machine is paused when the history runs out
+            if let Some(state) = rewind.step_back() {
+                if let Err(message) = machine.load_state(&state) {
+                    eprintln!("{}", message);
+                }
+                if let Some(movie) = recording.as_mut() {
+                    movie.undo();
+                }
+            }
+            audio.set_playing(false);
+            machine.display_mut().refresh();
+            record_gif_frame(&mut gif, &machine);
+            machine.wait_for_next_frame();
+            continue;
+        }
+        // the player takes over the keys until the movie is over
+        let mut keypad = live_keys;
+        if let Some(movie_player) = player.as_mut() {
+            if let Ok(keys) = movie_player.poll() {
+                keypad = keys;
+            }
+            if movie_player.finished() {
+                eprintln!("Movie finished");
+                player = None;
+            }
+        }
+        if let Some(movie) = recording.as_mut() {
+            movie.record(&keypad);
+        }
+        machine.set_keys(keypad);
+        match machine.run_frame() {
+            Ok(StepOutcome::Continue) => (),
+            Ok(StepOutcome::Exit) => break,
+            Err(error) => {
+                eprintln!("{}", error);
+                exit_code = 1;
+                break;
+            }
+        }
+        audio.set_pattern(machine.cpu().audio_pattern(), machine.cpu().pitch());
+        audio.set_playing(machine.cpu().sound_timer() > 0);
+        rewind.push(machine.save_state());
+        record_gif_frame(&mut gif, &machine);
+        machine.wait_for_next_frame();
+    }
+    if let Some(recorder) = gif.take() {
+        if let Err(message) = finish_gif(recorder) {
+            eprintln!("{}", message);
+        }
+    }
+    // also written when the program crashed, so the crash can be replayed
+    if let (Some(movie), Some(filename)) = (&recording, &options.record) {
+        if let Err(message) = movie.write(filename) {
+            eprintln!("{}", message);
+            exit_code = 1;
+        }
+    }
+    if exit_code != 0 {
+        process::exit(exit_code);
+    }
+}
+
+// runs as fast as possible without a window or sound and prints the final screen
+fn run_headless(machine: &mut Machine<HeadlessDisplay>, keypad: &mut dyn Keypad, options: &Options, frames: u32) {
+    let mut gif = options.gif.as_ref().map(|filename| match GifRecorder::create(filename, &options.display.palette) {
+        Ok(recorder) => recorder,
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(1);
+        }
+    });
+    for _ in 0..frames {
+        match machine.run_frame_with(keypad) {
+            Ok(Ok(StepOutcome::Continue)) => (),
+            Ok(Ok(StepOutcome::Exit)) | Err(Quit) => break,
+            Ok(Err(error)) => {
+                eprintln!("{}", error);
+                process::exit(1);
+            }
+        }
+        record_gif_frame(&mut gif, machine);
+    }
+    if let Some(recorder) = gif.take() {
+        if let Err(message) = finish_gif(recorder) {
+            eprintln!("{}", message);
+            process::exit(1);
+        }
+    }
+    let display = machine.display();
+    let bits = display.bits();
+    for row in bits.chunks(display.width()) {
+        let line: String = row.iter().map(|bit| if *bit { '#' } else { '.' }).collect();
+        println!("{}", line);
+    }
+    eprintln!("{} frames, {}x{}", display.refreshes(), display.width(), display.height());
+    if let Some(filename) = &options.screenshot {
+        let format = if filename.ends_with(".pbm") { ImageFormat::Pbm } else { ImageFormat::Png };
+        let palette = options.display.palette.map(|color| color.rgb());
+        if let Err(message) = display.save_screenshot(filename, format, &palette, options.display.scale) {
+            eprintln!("{}", message);
+            process::exit(1);
+        }
+    }
+}
 
-    while let Ok(keypad) = input.poll(){
-        if !cpu.tick(&mut ram, Some(&keypad), Some(&mut display)) {
-            break;
+// runs in real time like the window, without sound, save states and rewinding
+#[cfg(all(unix, feature = "tui"))]
+fn run_terminal(cpu: CPU, ram: RAM, mut player: Option<MoviePlayer>, settings: Settings, keymap: &Keymap, options: &Options) -> Result<(), String> {
+    let mut keypad = TerminalKeypad::new(keymap);
+    keypad.enter_raw_mode()?;
+    let mut recording = options.record.as_ref().map(|_| Movie::new(cpu.seed(), settings));
+    let mut machine = Machine::new(cpu, ram, TerminalDisplay::new(&options.display), options.clock_speed);
+    let mut gif = match &options.gif {
+        Some(filename) => Some(GifRecorder::create(filename, &options.display.palette)?),
+        None => None
+    };
+    let mut result = Ok(());
+    while let Ok(live_keys) = keypad.poll() {
+        let mut keys = live_keys;
+        if let Some(movie_player) = player.as_mut() {
+            if let Ok(movie_keys) = movie_player.poll() {
+                keys = movie_keys;
+            }
+            if movie_player.finished() {
+                player = None;
+            }
+        }
+        if let Some(movie) = recording.as_mut() {
+            movie.record(&keys);
         }
-        display.refresh();
+        machine.set_keys(keys);
+        match machine.run_frame() {
+            Ok(StepOutcome::Continue) => (),
+            Ok(StepOutcome::Exit) => break,
+            Err(error) => {
+                result = Err(error.to_string());
+                break;
+            }
+        }
+        record_gif_frame(&mut gif, &machine);
+        machine.wait_for_next_frame();
+    }
+    if let Some(recorder) = gif.take() {
+        result = result.and(finish_gif(recorder));
     }
+    if let (Some(movie), Some(filename)) = (&recording, &options.record) {
+        result = result.and(movie.write(filename));
+    }
+    result
+}
+
+// a failing recording is stopped, the game keeps running
+fn record_gif_frame<D: Display>(gif: &mut Option<GifRecorder<BufWriter<File>>>, machine: &Machine<D>) {
+    if let Some(recorder) = gif.as_mut() {
+        if let Err(message) = recorder.add_frame(machine.framebuffer()) {
+            eprintln!("{}", message);
+            *gif = None;
+        }
+    }
+}
+
+fn finish_gif(recorder: GifRecorder<BufWriter<File>>) -> Result<(), String> {
+    recorder.finish().map(|_| eprintln!("Finished the GIF"))
+}
+
+// save states are stored next to the ROM, one file per slot
+fn state_path(program: &str, slot: u8) -> String {
+    format!("{}.state{}", program, slot)
 }
@@ -0,0 +1,230 @@
+use std::fs;
+use chippie_ate::{Keypad, Quirks, Quit};
+
+const MAGIC: &[u8; 4] = b"C8MV";
+// increase whenever the layout of the file changes
+const VERSION: u8 = 2;
+const HEADER_SIZE: usize = 23;
+
+
+// the configuration a movie has to be played back with to stay in sync
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Settings {
+    pub clock_speed: u32,
+    pub quirks: Quirks,
+    pub xo_chip: bool,
+}
+
+// the keys of every frame, the seed of the random numbers and the settings, which is all that is needed to
+// replay a run
+#[derive(Debug, PartialEq)]
+pub struct Movie {
+    seed: u64,
+    settings: Settings,
+    // one bit per key
+    frames: Vec<u16>,
+}
+
+impl Movie {
+    pub fn new(seed: u64, settings: Settings) -> Self {
+        Movie { seed, settings, frames: Vec::new() }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // the options have to match the recording, otherwise the program soon does something else
+    pub fn check_settings(&self, settings: &Settings) -> Result<(), String> {
+        let recorded = &self.settings;
+        if recorded.clock_speed != settings.clock_speed {
+            return Err(format!("The movie was recorded with --speed {}", recorded.clock_speed));
+        }
+        if recorded.quirks != settings.quirks {
+            return Err("The movie was recorded with different --quirks".to_string());
+        }
+        if recorded.xo_chip != settings.xo_chip {
+            let xo = if recorded.xo_chip { "with" } else { "without" };
+            return Err(format!("The movie was recorded {} --xo", xo));
+        }
+        Ok(())
+    }
+
+    pub fn record(&mut self, keys: &[bool; 16]) {
+        let mask = keys.iter().enumerate()
+            .filter(|(_, pressed)| **pressed)
+            .fold(0, |mask, (key, _)| mask | 1 << key);
+        self.frames.push(mask);
+    }
+
+    // forget the newest frame, used when rewinding
+    pub fn undo(&mut self) {
+        self.frames.pop();
+    }
+
+    pub fn keys(&self, frame: usize) -> Option<[bool; 16]> {
+        let mask = self.frames.get(frame)?;
+        let mut keys = [false; 16];
+        for (key, pressed) in keys.iter_mut().enumerate() {
+            *pressed = mask & (1 << key) != 0;
+        }
+        Some(keys)
+    }
+
+    // little endian: magic, version, seed, clock speed, quirks, XO-CHIP flag, number of frames and the key masks
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + self.frames.len() * 2);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.extend_from_slice(&self.settings.clock_speed.to_le_bytes());
+        bytes.push(self.settings.quirks.to_bits());
+        bytes.push(self.settings.xo_chip as u8);
+        bytes.extend_from_slice(&(self.frames.len() as u32).to_le_bytes());
+        for mask in &self.frames {
+            bytes.extend_from_slice(&mask.to_le_bytes());
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < HEADER_SIZE || &bytes[0..4] != MAGIC {
+            return Err("Not a movie file".to_string());
+        }
+        if bytes[4] != VERSION {
+            return Err(format!("Unsupported movie version {}", bytes[4]));
+        }
+        let mut seed = [0; 8];
+        seed.copy_from_slice(&bytes[5..13]);
+        let settings = Settings {
+            clock_speed: u32::from_le_bytes([bytes[13], bytes[14], bytes[15], bytes[16]]),
+            quirks: Quirks::from_bits(bytes[17]),
+            xo_chip: bytes[18] != 0,
+        };
+        let length = u32::from_le_bytes([bytes[19], bytes[20], bytes[21], bytes[22]]) as usize;
+        let data = &bytes[HEADER_SIZE..];
+        if data.len() != length * 2 {
+            return Err(format!("Movie should have {} frames, but has {} bytes of frames", length, data.len()));
+        }
+        Ok(Movie {
+            seed: u64::from_le_bytes(seed),
+            settings,
+            frames: data.chunks(2).map(|mask| u16::from_le_bytes([mask[0], mask[1]])).collect(),
+        })
+    }
+
+    pub fn read(filename: &str) -> Result<Self, String> {
+        match fs::read(filename) {
+            Ok(bytes) => Movie::from_bytes(&bytes),
+            Err(e) => Err(format!("Failed to read {}: {}", filename, e))
+        }
+    }
+
+    pub fn write(&self, filename: &str) -> Result<(), String> {
+        match fs::write(filename, self.to_bytes()) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Failed to write {}: {}", filename, e))
+        }
+    }
+}
+
+// feeds the frames of a movie to the CPU, stops when the movie is over
+pub struct MoviePlayer {
+    movie: Movie,
+    frame: usize,
+}
+
+impl MoviePlayer {
+    pub fn new(movie: Movie) -> Self {
+        MoviePlayer { movie, frame: 0 }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.frame >= self.movie.frames.len()
+    }
+}
+
+impl Keypad for MoviePlayer {
+    fn poll(&mut self) -> Result<[bool; 16], Quit> {
+        let keys = self.movie.keys(self.frame).ok_or(Quit)?;
+        self.frame += 1;
+        Ok(keys)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chippie_ate::{Keypad, Quirks, Quit};
+    use crate::movie::{Movie, MoviePlayer, Settings};
+
+    fn settings() -> Settings {
+        Settings { clock_speed: 700, quirks: Quirks::cosmac_vip(), xo_chip: false }
+    }
+
+    fn keys(pressed: &[usize]) -> [bool; 16] {
+        let mut keys = [false; 16];
+        for key in pressed {
+            keys[*key] = true;
+        }
+        keys
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut movie = Movie::new(0xDEAD_BEEF, settings());
+        movie.record(&keys(&[]));
+        movie.record(&keys(&[0x0, 0xF]));
+        movie.record(&keys(&[0x5]));
+        movie.undo();
+        movie.record(&keys(&[0x6]));
+
+        let bytes = movie.to_bytes();
+        assert_eq!(bytes.len(), 23 + 3 * 2);
+        assert_eq!(&bytes[23..], [0x00, 0x00, 0x01, 0x80, 0x40, 0x00]);
+        let loaded = Movie::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded, movie);
+        assert_eq!(loaded.seed(), 0xDEAD_BEEF);
+    }
+
+    #[test]
+    fn test_invalid_movie() {
+        let mut bytes = Movie::new(1, settings()).to_bytes();
+        assert_eq!(Movie::from_bytes(&bytes[..22]).err().unwrap(), "Not a movie file");
+        bytes.push(0);
+        assert_eq!(Movie::from_bytes(&bytes).err().unwrap(), "Movie should have 0 frames, but has 1 bytes of frames");
+        bytes[4] = 1;
+        assert_eq!(Movie::from_bytes(&bytes).err().unwrap(), "Unsupported movie version 1");
+    }
+
+    #[test]
+    fn test_playback() {
+        let mut movie = Movie::new(1, settings());
+        movie.record(&keys(&[0x1]));
+        movie.record(&keys(&[0x2, 0x3]));
+        let mut player = MoviePlayer::new(movie);
+        assert_eq!(player.poll(), Ok(keys(&[0x1])));
+        assert!(!player.finished());
+        assert_eq!(player.poll(), Ok(keys(&[0x2, 0x3])));
+        assert!(player.finished());
+        assert_eq!(player.poll(), Err(Quit));
+    }
+
+    #[test]
+    fn test_check_settings() {
+        let movie = Movie::from_bytes(&Movie::new(1, settings()).to_bytes()).unwrap();
+        assert_eq!(movie.check_settings(&settings()), Ok(()));
+        assert_eq!(
+            movie.check_settings(&Settings { clock_speed: 1000, ..settings() }).expect_err(""),
+            "The movie was recorded with --speed 700"
+        );
+        assert_eq!(
+            movie.check_settings(&Settings { quirks: Quirks::modern(), ..settings() }).expect_err(""),
+            "The movie was recorded with different --quirks"
+        );
+        assert_eq!(
+            movie.check_settings(&Settings { xo_chip: true, ..settings() }).expect_err(""),
+            "The movie was recorded without --xo"
+        );
+    }
+}
@@ -0,0 +1,424 @@
+use sdl2::pixels::Color;
+use chippie_ate::{Quirks, DEFAULT_CLOCK_SPEED};
+use crate::frontend::{palette, DisplayOptions, RenderMode, DEFAULT_DECAY, DEFAULT_PITCH, DEFAULT_VOLUME, MAX_PITCH, PALETTE_NAMES, RENDER_MODE_NAMES};
+
+// how far back in time holding backspace can go
+pub const DEFAULT_REWIND_SECONDS: u32 = 300;
+// an hour, which keeps the number of frames to store far from overflowing
+pub const MAX_REWIND_SECONDS: u32 = 3600;
+
+
+// settings provided on the command line
+#[derive(Debug, PartialEq)]
+pub struct Options {
+    pub program: String,
+    pub clock_speed: u32,
+    pub quirks: Quirks,
+    // XO-CHIP programs get 64 KiB of memory
+    pub xo_chip: bool,
+    pub pitch: f32,
+    pub volume: f32,
+    // 0 disables rewinding
+    pub rewind_seconds: u32,
+    // run this many frames without a window and print the screen
+    pub headless_frames: Option<u32>,
+    // draw in the terminal instead of a window
+    pub tui: bool,
+    // key presses to replay in headless mode
+    pub key_script: Option<String>,
+    // image of the last frame of a headless run
+    pub screenshot: Option<String>,
+    // record everything that is shown to a GIF
+    pub gif: Option<String>,
+    // movie files to write the keys to or to replay them from
+    pub record: Option<String>,
+    pub play: Option<String>,
+    // random numbers are different every run unless a seed is given
+    pub seed: Option<u64>,
+    pub keymap: Option<String>,
+    pub display: DisplayOptions,
+}
+
+impl Options {
+    // parse the arguments following the executable name
+    pub fn parse(args: &[String]) -> Result<Options, String> {
+        let mut program = None;
+        let mut clock_speed = DEFAULT_CLOCK_SPEED;
+        let mut quirks = None;
+        let mut xo_chip = false;
+        let mut clip_sprites = None;
+        let mut legacy_key_wait = false;
+        let mut pitch = DEFAULT_PITCH;
+        let mut volume = DEFAULT_VOLUME;
+        let mut rewind_seconds = DEFAULT_REWIND_SECONDS;
+        let mut headless_frames = None;
+        let mut tui = false;
+        let mut key_script = None;
+        let mut screenshot = None;
+        let mut gif = None;
+        let mut record = None;
+        let mut play = None;
+        let mut seed = None;
+        let mut keymap = None;
+        let mut display = DisplayOptions::default();
+        let mut foreground = None;
+        let mut background = None;
+        let mut render_mode = None;
+        let mut decay = DEFAULT_DECAY;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--speed" => {
+                    clock_speed = parse_value(arg, args.next())?;
+                    if clock_speed == 0 {
+                        return Err("--speed needs to be at least 1".to_string());
+                    }
+                }
+                "--quirks" => {
+                    let name: String = parse_value(arg, args.next())?;
+                    quirks = match Quirks::from_name(&name) {
+                        Some(quirks) => Some(quirks),
+                        None => return Err(format!("Unknown quirks profile '{}', use vip, chip48, schip or modern", name))
+                    };
+                }
+                "--xo" => xo_chip = true,
+                "--clip" => clip_sprites = Some(true),
+                "--wrap" => clip_sprites = Some(false),
+                "--legacy-key-wait" => legacy_key_wait = true,
+                "--pitch" => {
+                    pitch = parse_value(arg, args.next())?;
+                    if !(pitch > 0.0 && pitch < MAX_PITCH) {
+                        return Err(format!("--pitch needs to be more than 0 and less than {}", MAX_PITCH));
+                    }
+                }
+                "--volume" => {
+                    volume = parse_value(arg, args.next())?;
+                    if !(0.0..=1.0).contains(&volume) {
+                        return Err("--volume needs to be at least 0 and at most 1".to_string());
+                    }
+                }
+                "--rewind" => {
+                    rewind_seconds = parse_value(arg, args.next())?;
+                    if rewind_seconds > MAX_REWIND_SECONDS {
+                        return Err(format!("--rewind can be at most {} seconds", MAX_REWIND_SECONDS));
+                    }
+                }
+                "--headless" => headless_frames = Some(parse_value(arg, args.next())?),
+                "--tui" if cfg!(all(unix, feature = "tui")) => tui = true,
+                "--tui" => return Err("--tui needs a Unix terminal and the tui feature".to_string()),
+                "--keys" => key_script = Some(parse_value(arg, args.next())?),
+                "--screenshot" => screenshot = Some(parse_value(arg, args.next())?),
+                "--gif" => gif = Some(parse_value(arg, args.next())?),
+                "--record" => record = Some(parse_value(arg, args.next())?),
+                "--play" => play = Some(parse_value(arg, args.next())?),
+                "--seed" => seed = Some(parse_value(arg, args.next())?),
+                "--keymap" => keymap = Some(parse_value(arg, args.next())?),
+                "--palette" => {
+                    let name: String = parse_value(arg, args.next())?;
+                    display.palette = match palette(&name) {
+                        Some(palette) => palette,
+                        None => return Err(format!("Unknown palette '{}', use {}", name, PALETTE_NAMES.join(", ")))
+                    };
+                }
+                "--fg" => foreground = Some(parse_color(arg, args.next())?),
+                "--bg" => background = Some(parse_color(arg, args.next())?),
+                "--scale" => {
+                    display.scale = parse_value(arg, args.next())?;
+                    if display.scale == 0 {
+                        return Err("--scale needs to be at least 1".to_string());
+                    }
+                }
+                "--fullscreen" => display.fullscreen = true,
+                "--resizable" => display.resizable = true,
+                "--render" => render_mode = Some(parse_value::<String>(arg, args.next())?),
+                "--decay" => {
+                    decay = parse_value(arg, args.next())?;
+                    if !(0.0..1.0).contains(&decay) {
+                        return Err("--decay needs to be at least 0 and less than 1".to_string());
+                    }
+                }
+                _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+                _ => {
+                    if program.is_some() {
+                        return Err(format!("Unexpected argument {}", arg));
+                    }
+                    program = Some(arg.clone());
+                }
+            }
+        }
+        if record.is_some() && play.is_some() {
+            return Err("Use either --record or --play".to_string());
+        }
+        // headless runs replay a movie or a key script, there are no live keys to record
+        if headless_frames.is_some() && record.is_some() {
+            return Err("--record can't be used with --headless".to_string());
+        }
+        if headless_frames.is_some() && tui {
+            return Err("Use either --headless or --tui".to_string());
+        }
+        // the window takes screenshots with F12 instead
+        if screenshot.is_some() && headless_frames.is_none() {
+            return Err("--screenshot needs --headless".to_string());
+        }
+        // live runs read the keys from the keyboard
+        if key_script.is_some() && headless_frames.is_none() {
+            return Err("--keys needs --headless".to_string());
+        }
+        let program = match program {
+            Some(program) => program,
+            None => return Err("No program file provided!".to_string())
+        };
+        // XO-CHIP programs are written with Octo and expect its behavior unless told otherwise
+        let mut quirks = match quirks {
+            Some(quirks) => quirks,
+            None if xo_chip => Quirks::modern(),
+            None => Quirks::default()
+        };
+        // explicit clipping options take precedence over the profile
+        if let Some(clip_sprites) = clip_sprites {
+            quirks.clip_sprites = clip_sprites;
+        }
+        if let Some(name) = render_mode {
+            display.render_mode = match RenderMode::from_name(&name, decay) {
+                Some(mode) => mode,
+                None => return Err(format!("Unknown render mode '{}', use {}", name, RENDER_MODE_NAMES.join(", ")))
+            };
+        }
+        // single colors take precedence over the palette
+        if let Some(color) = background {
+            display.palette[0] = color;
+        }
+        if let Some(color) = foreground {
+            display.palette[1] = color;
+        }
+        if legacy_key_wait {
+            quirks.key_wait_on_press = true;
+        }
+        Ok(Options {
+            program,
+            clock_speed,
+            quirks,
+            xo_chip,
+            pitch,
+            volume,
+            rewind_seconds,
+            headless_frames,
+            tui,
+            key_script,
+            screenshot,
+            gif,
+            record,
+            play,
+            seed,
+            keymap,
+            display,
+        })
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(option: &str, value: Option<&String>) -> Result<T, String> {
+    let value = match value {
+        Some(value) => value,
+        None => return Err(format!("Missing value for {}", option))
+    };
+    match value.parse() {
+        Ok(v) => Ok(v),
+        Err(_) => Err(format!("Invalid value '{}' for {}", value, option))
+    }
+}
+
+// RRGGBB in hex, with or without a leading #
+fn parse_color(option: &str, value: Option<&String>) -> Result<Color, String> {
+    let value: String = parse_value(option, value)?;
+    let hex = value.strip_prefix('#').unwrap_or(&value);
+    match u32::from_str_radix(hex, 16) {
+        Ok(rgb) if hex.len() == 6 => Ok(Color::RGB((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)),
+        _ => Err(format!("Invalid color '{}' for {}, use RRGGBB", value, option))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use sdl2::pixels::Color;
+    use chippie_ate::{Quirks, DEFAULT_CLOCK_SPEED};
+    use crate::frontend::{DisplayOptions, RenderMode};
+    use crate::options::{Options, DEFAULT_REWIND_SECONDS, MAX_REWIND_SECONDS};
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_program() {
+        let options = Options::parse(&args(&["pong.ch8"])).unwrap();
+        assert_eq!(options.program, "pong.ch8");
+        assert_eq!(options.clock_speed, DEFAULT_CLOCK_SPEED);
+        assert_eq!(options.quirks, Quirks::default());
+        assert!(!options.xo_chip);
+        assert_eq!(options.rewind_seconds, DEFAULT_REWIND_SECONDS);
+        assert_eq!(options.headless_frames, None);
+        assert!(!options.tui);
+        assert_eq!(options.seed, None);
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let options = Options::parse(&args(&["--speed", "1000", "pong.ch8", "--clip", "--rewind", "0"])).unwrap();
+        assert_eq!(options.program, "pong.ch8");
+        assert_eq!(options.clock_speed, 1000);
+        assert!(options.quirks.clip_sprites);
+        assert_eq!(options.rewind_seconds, 0);
+
+        let options = Options::parse(&args(&["--headless", "120", "pong.ch8", "--keys", "menu.txt"])).unwrap();
+        assert_eq!(options.headless_frames, Some(120));
+        assert_eq!(options.key_script, Some("menu.txt".to_string()));
+        assert_eq!(options.screenshot, None);
+
+        let options = Options::parse(&args(&["--headless", "60", "pong.ch8", "--screenshot", "pong.png"])).unwrap();
+        assert_eq!(options.screenshot, Some("pong.png".to_string()));
+        assert_eq!(
+            Options::parse(&args(&["pong.ch8", "--screenshot", "pong.png"])).expect_err(""),
+            "--screenshot needs --headless"
+        );
+        assert_eq!(Options::parse(&args(&["pong.ch8", "--keys", "menu.txt"])).expect_err(""), "--keys needs --headless");
+
+        let options = Options::parse(&args(&["pong.ch8", "--gif", "pong.gif"])).unwrap();
+        assert_eq!(options.gif, Some("pong.gif".to_string()));
+
+        let options = Options::parse(&args(&["--seed", "1234", "pong.ch8"])).unwrap();
+        assert_eq!(options.seed, Some(1234));
+
+        let options = Options::parse(&args(&["--keymap", "keys.toml", "pong.ch8"])).unwrap();
+        assert_eq!(options.keymap, Some("keys.toml".to_string()));
+    }
+
+    #[test]
+    fn test_parse_xo_chip() {
+        let options = Options::parse(&args(&["--xo", "game.xo8"])).unwrap();
+        assert!(options.xo_chip);
+        assert_eq!(options.quirks, Quirks::modern());
+        let options = Options::parse(&args(&["--xo", "--quirks", "schip", "game.xo8"])).unwrap();
+        assert_eq!(options.quirks, Quirks::superchip());
+    }
+
+    #[test]
+    fn test_parse_quirks() {
+        let options = Options::parse(&args(&["--quirks", "vip", "--wrap", "pong.ch8"])).unwrap();
+        assert!(options.quirks.shift_uses_vy);
+        assert!(!options.quirks.clip_sprites);
+        assert!(!options.quirks.key_wait_on_press);
+        let options = Options::parse(&args(&["--legacy-key-wait", "pong.ch8"])).unwrap();
+        assert!(options.quirks.key_wait_on_press);
+        assert_eq!(
+            Options::parse(&args(&["--quirks", "octo", "pong.ch8"])).expect_err(""),
+            "Unknown quirks profile 'octo', use vip, chip48, schip or modern"
+        );
+    }
+
+    #[test]
+    fn test_parse_movie() {
+        let options = Options::parse(&args(&["pong.ch8", "--record", "pong.c8m"])).unwrap();
+        assert_eq!(options.record, Some("pong.c8m".to_string()));
+        assert_eq!(options.play, None);
+        assert_eq!(
+            Options::parse(&args(&["pong.ch8", "--record", "a.c8m", "--play", "b.c8m"])).expect_err(""),
+            "Use either --record or --play"
+        );
+        assert_eq!(
+            Options::parse(&args(&["pong.ch8", "--headless", "60", "--record", "a.c8m"])).expect_err(""),
+            "--record can't be used with --headless"
+        );
+        let options = Options::parse(&args(&["pong.ch8", "--headless", "60", "--play", "a.c8m"])).unwrap();
+        assert_eq!(options.play, Some("a.c8m".to_string()));
+    }
+
+    #[test]
+    #[cfg(all(unix, feature = "tui"))]
+    fn test_parse_tui() {
+        let options = Options::parse(&args(&["--tui", "pong.ch8", "--palette", "amber"])).unwrap();
+        assert!(options.tui);
+        assert_eq!(
+            Options::parse(&args(&["pong.ch8", "--tui", "--headless", "60"])).expect_err(""),
+            "Use either --headless or --tui"
+        );
+    }
+
+    #[test]
+    #[cfg(not(all(unix, feature = "tui")))]
+    fn test_parse_tui() {
+        assert_eq!(Options::parse(&args(&["--tui", "pong.ch8"])).expect_err(""), "--tui needs a Unix terminal and the tui feature");
+    }
+
+    #[test]
+    fn test_parse_display() {
+        let options = Options::parse(&args(&["pong.ch8", "--palette", "white", "--fg", "#FF8000", "--scale", "8", "--resizable"])).unwrap();
+        assert_eq!(options.display.palette[0], Color::RGB(0, 0, 0));
+        assert_eq!(options.display.palette[1], Color::RGB(255, 128, 0));
+        assert_eq!(options.display.palette[2], Color::RGB(170, 170, 170));
+        assert_eq!(options.display.scale, 8);
+        assert!(options.display.resizable);
+        assert!(!options.display.fullscreen);
+
+        let options = Options::parse(&args(&["pong.ch8", "--bg", "102030"])).unwrap();
+        assert_eq!(options.display.palette[0], Color::RGB(0x10, 0x20, 0x30));
+        assert_eq!(options.display.scale, DisplayOptions::default().scale);
+    }
+
+    #[test]
+    fn test_parse_render_mode() {
+        let options = Options::parse(&args(&["pong.ch8"])).unwrap();
+        assert_eq!(options.display.render_mode, RenderMode::Plain);
+        let options = Options::parse(&args(&["pong.ch8", "--render", "blend"])).unwrap();
+        assert_eq!(options.display.render_mode, RenderMode::Blend);
+        let options = Options::parse(&args(&["--decay", "0.8", "pong.ch8", "--render", "phosphor"])).unwrap();
+        assert_eq!(options.display.render_mode, RenderMode::Phosphor(0.8));
+        assert_eq!(
+            Options::parse(&args(&["pong.ch8", "--render", "crt"])).expect_err(""),
+            "Unknown render mode 'crt', use plain, blend, phosphor"
+        );
+        assert_eq!(
+            Options::parse(&args(&["pong.ch8", "--decay", "1.5"])).expect_err(""),
+            "--decay needs to be at least 0 and less than 1"
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_display() {
+        assert_eq!(
+            Options::parse(&args(&["pong.ch8", "--palette", "pink"])).expect_err(""),
+            "Unknown palette 'pink', use green, white, amber, lcd, octo"
+        );
+        assert_eq!(Options::parse(&args(&["pong.ch8", "--fg", "red"])).expect_err(""), "Invalid color 'red' for --fg, use RRGGBB");
+        assert_eq!(Options::parse(&args(&["pong.ch8", "--scale", "0"])).expect_err(""), "--scale needs to be at least 1");
+    }
+
+    #[test]
+    fn test_parse_audio() {
+        let options = Options::parse(&args(&["pong.ch8", "--pitch", "220", "--volume", "1"])).unwrap();
+        assert_eq!(options.pitch, 220.0);
+        assert_eq!(options.volume, 1.0);
+        let error = "--pitch needs to be more than 0 and less than 22050";
+        assert_eq!(Options::parse(&args(&["pong.ch8", "--pitch", "0"])).expect_err(""), error);
+        assert_eq!(Options::parse(&args(&["pong.ch8", "--pitch", "-440"])).expect_err(""), error);
+        assert_eq!(Options::parse(&args(&["pong.ch8", "--pitch", "30000"])).expect_err(""), error);
+        assert_eq!(Options::parse(&args(&["pong.ch8", "--pitch", "NaN"])).expect_err(""), error);
+        let error = "--volume needs to be at least 0 and at most 1";
+        assert_eq!(Options::parse(&args(&["pong.ch8", "--volume", "1.5"])).expect_err(""), error);
+        assert_eq!(Options::parse(&args(&["pong.ch8", "--volume", "-0.1"])).expect_err(""), error);
+    }
+
+    #[test]
+    fn test_parse_missing_program() {
+        assert_eq!(Options::parse(&args(&["--clip"])).expect_err(""), "No program file provided!");
+    }
+
+    #[test]
+    fn test_parse_invalid_value() {
+        assert_eq!(Options::parse(&args(&["pong.ch8", "--speed", "fast"])).expect_err(""), "Invalid value 'fast' for --speed");
+        assert_eq!(Options::parse(&args(&["pong.ch8", "--speed"])).expect_err(""), "Missing value for --speed");
+        let options = Options::parse(&args(&["pong.ch8", "--rewind", &MAX_REWIND_SECONDS.to_string()])).unwrap();
+        assert_eq!(options.rewind_seconds, MAX_REWIND_SECONDS);
+        assert_eq!(Options::parse(&args(&["pong.ch8", "--rewind", "4294967295"])).expect_err(""), "--rewind can be at most 3600 seconds");
+    }
+}
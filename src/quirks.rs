@@ -0,0 +1,128 @@
+// instructions that were interpreted differently by the CHIP-8 interpreters over the years
+
+
+// the default, with every quirk off, is the behavior this emulator always had, apart from Fx0A waiting for a
+// key to be released
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Quirks {
+    // 8xy6 and 8xyE shift Vy and store the result in Vx instead of shifting Vx in place
+    pub shift_uses_vy: bool,
+    // Fx55 and Fx65 leave I pointing after the last register that was copied
+    pub load_store_increments_i: bool,
+    // Bnnn jumps to nnn plus Vx, x being the highest nibble of nnn, instead of nnn plus V0
+    pub jump_uses_vx: bool,
+    // 8xy1, 8xy2 and 8xy3 reset VF to 0
+    pub logic_resets_vf: bool,
+    // sprites are clipped at the screen edges instead of wrapping around
+    pub clip_sprites: bool,
+    // Fx0A finishes as soon as any key is held, instead of waiting for a key to be pressed and released
+    pub key_wait_on_press: bool,
+}
+
+impl Quirks {
+    // the original interpreter of the RCA COSMAC VIP
+    pub fn cosmac_vip() -> Self {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_uses_vx: false,
+            logic_resets_vf: true,
+            clip_sprites: true,
+            key_wait_on_press: false,
+        }
+    }
+
+    // CHIP-48 on the HP-48 calculators
+    pub fn chip48() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_increments_i: true,
+            jump_uses_vx: true,
+            logic_resets_vf: false,
+            clip_sprites: true,
+            key_wait_on_press: false,
+        }
+    }
+
+    // SUPER-CHIP 1.1
+    pub fn superchip() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_increments_i: false,
+            jump_uses_vx: true,
+            logic_resets_vf: false,
+            clip_sprites: true,
+            key_wait_on_press: false,
+        }
+    }
+
+    // what most modern games written with Octo expect
+    pub fn modern() -> Self {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_uses_vx: false,
+            logic_resets_vf: false,
+            clip_sprites: false,
+            key_wait_on_press: false,
+        }
+    }
+
+    // one bit per quirk in the order of the fields, for files that have to be replayed with the same quirks
+    pub fn to_bits(&self) -> u8 {
+        [
+            self.shift_uses_vy,
+            self.load_store_increments_i,
+            self.jump_uses_vx,
+            self.logic_resets_vf,
+            self.clip_sprites,
+            self.key_wait_on_press,
+        ].iter().enumerate().fold(0, |bits, (bit, set)| bits | (*set as u8) << bit)
+    }
+
+    pub fn from_bits(bits: u8) -> Self {
+        let bit = |n: u8| bits & (1 << n) != 0;
+        Quirks {
+            shift_uses_vy: bit(0),
+            load_store_increments_i: bit(1),
+            jump_uses_vx: bit(2),
+            logic_resets_vf: bit(3),
+            clip_sprites: bit(4),
+            key_wait_on_press: bit(5),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "vip" | "cosmac" => Some(Quirks::cosmac_vip()),
+            "chip48" => Some(Quirks::chip48()),
+            "schip" | "superchip" => Some(Quirks::superchip()),
+            "modern" => Some(Quirks::modern()),
+            _ => None
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::quirks::Quirks;
+
+    #[test]
+    fn test_quirks_from_name() {
+        assert_eq!(Quirks::from_name("vip"), Some(Quirks::cosmac_vip()));
+        assert_eq!(Quirks::from_name("schip"), Some(Quirks::superchip()));
+        assert_eq!(Quirks::from_name("unknown"), None);
+    }
+
+    #[test]
+    fn test_bits() {
+        assert_eq!(Quirks::default().to_bits(), 0);
+        assert_eq!(Quirks::cosmac_vip().to_bits(), 0b011011);
+        for quirks in [Quirks::cosmac_vip(), Quirks::chip48(), Quirks::superchip(), Quirks::modern()] {
+            assert_eq!(Quirks::from_bits(quirks.to_bits()), quirks);
+        }
+        let quirks = Quirks { key_wait_on_press: true, ..Quirks::default() };
+        assert_eq!(Quirks::from_bits(quirks.to_bits()), quirks);
+    }
+}
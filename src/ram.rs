@@ -1,19 +1,35 @@
+use crate::save_state::{StateReader, StateWriter};
+
 pub const RAM_OFFSET: usize = 0x200;
 // 4096
 pub const RAM_SIZE: usize = 0x1000;
+// 65536, XO-CHIP can address all memory with a 16 bit I
+pub const XO_RAM_SIZE: usize = 0x10000;
 
+#[derive(Clone)]
 pub struct RAM {
-    // 4096 bytes
-    memory: [u8; RAM_SIZE],
+    // 4096 bytes or 64 KiB for XO-CHIP
+    memory: Vec<u8>,
     // 512 bytes reserved --> 0x200
 }
 
+impl Default for RAM {
+    fn default() -> Self {
+        RAM::new()
+    }
+}
+
 impl RAM {
     pub fn new() -> Self {
-        let memory = [0; RAM_SIZE];
+        RAM::with_size(RAM_SIZE)
+    }
+
+    pub fn with_size(size: usize) -> Self {
+        let memory = vec![0; size];
         let mut ram = RAM { memory };
         // set all the letters in memory
         ram.sets(0, &LETTERS);
+        ram.sets(LARGE_LETTERS_OFFSET, &LARGE_LETTERS);
         ram
     }
 
@@ -29,11 +45,25 @@ impl RAM {
     }
 
     pub fn sets(&mut self, offset: usize, values: &[u8]) {
-        let mut count: usize = 0;
-        for val in values {
-            self.memory[offset + count] = *val;
-            count += 1;
+        self.memory[offset..offset + values.len()].copy_from_slice(values);
+    }
+
+    pub(crate) fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bytes(&self.memory);
+    }
+
+    pub(crate) fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        let memory = reader.read_bytes()?;
+        // an XO-CHIP state only fits an XO-CHIP machine and the other way around
+        if memory.len() != self.memory.len() {
+            return Err(format!("Save state has {} bytes of memory, but the machine has {}", memory.len(), self.memory.len()));
         }
+        self.memory = memory.to_vec();
+        Ok(())
+    }
+
+    pub fn size(&self) -> usize {
+        self.memory.len()
     }
 
     pub fn get(&self, offset: usize) -> u8 {
@@ -45,10 +75,10 @@ impl RAM {
     }
 
     pub fn _show(&self, from: usize, to: usize) {
-        if from % 2 != 0 {
+        if !from.is_multiple_of(2) {
             panic!("From argument needs to be even");
         }
-        if to % 2 != 0 {
+        if !to.is_multiple_of(2) {
             panic!("To argument needs to be even");
         }
         if from > to{
@@ -177,4 +207,43 @@ const LETTERS: [u8; 80] = [
     0xF0,
     0x80,
     0x80
-];
\ No newline at end of file
+];
+
+// the 8x10 SUPER-CHIP font directly follows the small font
+pub const LARGE_LETTERS_OFFSET: usize = LETTERS.len();
+pub const LARGE_LETTER_SIZE: usize = 10;
+// bytes
+const LARGE_LETTERS: [u8; 160] = [
+    // 0
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF,
+    // 1
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF,
+    // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF,
+    // 3
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF,
+    // 4
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03,
+    // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF,
+    // 6
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF,
+    // 7
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18,
+    // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF,
+    // 9
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF,
+    // A
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3,
+    // B
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC,
+    // C
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C,
+    // D
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC,
+    // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF,
+    // F
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0
+];
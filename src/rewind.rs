@@ -0,0 +1,157 @@
+use std::collections::VecDeque;
+
+
+// keeps the newest save state in full and every older frame as the difference to the frame after it.
+// Between two frames only a handful of bytes change, so the differences are XORed and run length encoded
+pub struct Rewind {
+    capacity: usize,
+    latest: Option<Vec<u8>>,
+    // oldest first, applying the last delta to latest gives the frame before it
+    deltas: VecDeque<Vec<u8>>,
+}
+
+impl Rewind {
+    // capacity is the number of frames that can be rewound
+    pub fn new(capacity: usize) -> Self {
+        Rewind {
+            capacity,
+            latest: None,
+            deltas: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, state: Vec<u8>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if let Some(latest) = self.latest.take() {
+            // the memory size changed after loading a save state, the older frames can't be restored anymore
+            if latest.len() != state.len() {
+                self.deltas.clear();
+            } else {
+                self.deltas.push_back(compress(&xor(&latest, &state)));
+                if self.deltas.len() > self.capacity {
+                    self.deltas.pop_front();
+                }
+            }
+        }
+        self.latest = Some(state);
+    }
+
+    // drops the newest frame and returns the one before it, the oldest frame is never dropped
+    pub fn step_back(&mut self) -> Option<Vec<u8>> {
+        let delta = self.deltas.pop_back()?;
+        let latest = self.latest.as_mut()?;
+        decompress(&delta, latest);
+        Some(latest.clone())
+    }
+}
+
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    a.iter().zip(b).map(|(a, b)| a ^ b).collect()
+}
+
+// a sequence of [number of zeros][number of literals][literals...], the counts are LEB128 encoded
+fn compress(delta: &[u8]) -> Vec<u8> {
+    let mut compressed = Vec::new();
+    let mut position = 0;
+    while position < delta.len() {
+        let zeros = delta[position..].iter().take_while(|value| **value == 0).count();
+        position += zeros;
+        let literals = delta[position..].iter().take_while(|value| **value != 0).count();
+        write_count(&mut compressed, zeros);
+        write_count(&mut compressed, literals);
+        compressed.extend_from_slice(&delta[position..position + literals]);
+        position += literals;
+    }
+    compressed
+}
+
+// XOR a compressed delta into the state
+fn decompress(compressed: &[u8], state: &mut [u8]) {
+    let mut input = compressed.iter().copied();
+    let mut position = 0;
+    while let Some(zeros) = read_count(&mut input) {
+        position += zeros;
+        let literals = read_count(&mut input).unwrap_or(0);
+        for value in input.by_ref().take(literals) {
+            state[position] ^= value;
+            position += 1;
+        }
+    }
+}
+
+fn write_count(output: &mut Vec<u8>, mut count: usize) {
+    while count >= 0x80 {
+        output.push((count as u8 & 0x7F) | 0x80);
+        count >>= 7;
+    }
+    output.push(count as u8);
+}
+
+fn read_count(input: &mut impl Iterator<Item = u8>) -> Option<usize> {
+    let mut count = 0;
+    let mut shift = 0;
+    loop {
+        let value = input.next()?;
+        count |= ((value & 0x7F) as usize) << shift;
+        if value & 0x80 == 0 {
+            return Some(count);
+        }
+        shift += 7;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::rewind::{compress, decompress, Rewind};
+
+    #[test]
+    fn test_compress() {
+        let mut delta = vec![0; 300];
+        delta[0] = 1;
+        delta[200] = 0xFF;
+        delta[201] = 0x0F;
+        let compressed = compress(&delta);
+        assert_eq!(compressed, [0, 1, 1, 0xC7, 0x01, 2, 0xFF, 0x0F, 98, 0]);
+
+        let mut state = vec![0; 300];
+        decompress(&compressed, &mut state);
+        assert_eq!(state, delta);
+    }
+
+    #[test]
+    fn test_step_back() {
+        let mut rewind = Rewind::new(10);
+        assert_eq!(rewind.step_back(), None);
+        rewind.push(vec![1, 2, 3, 4]);
+        rewind.push(vec![1, 2, 5, 4]);
+        rewind.push(vec![9, 2, 5, 0]);
+        assert_eq!(rewind.step_back(), Some(vec![1, 2, 5, 4]));
+        assert_eq!(rewind.step_back(), Some(vec![1, 2, 3, 4]));
+        assert_eq!(rewind.step_back(), None);
+        // continue from the rewound frame
+        rewind.push(vec![7, 7, 7, 7]);
+        assert_eq!(rewind.step_back(), Some(vec![1, 2, 3, 4]));
+    }
+
+    #[test]
+    fn test_capacity() {
+        let mut rewind = Rewind::new(2);
+        for value in 0..5 {
+            rewind.push(vec![value; 8]);
+        }
+        assert_eq!(rewind.step_back(), Some(vec![3; 8]));
+        assert_eq!(rewind.step_back(), Some(vec![2; 8]));
+        assert_eq!(rewind.step_back(), None);
+    }
+
+    #[test]
+    fn test_state_size_changes() {
+        let mut rewind = Rewind::new(10);
+        rewind.push(vec![1, 2]);
+        rewind.push(vec![1, 2, 3]);
+        assert_eq!(rewind.step_back(), None);
+    }
+}
@@ -0,0 +1,244 @@
+use std::fs;
+use crate::cpu::CPU;
+use crate::drivers::FrameBuffer;
+use crate::ram::RAM;
+
+const MAGIC: &[u8; 4] = b"C8SS";
+// increase whenever the layout of the state changes
+const VERSION: u8 = 3;
+
+
+// little endian encoding of the machine state
+pub struct StateWriter {
+    bytes: Vec<u8>,
+}
+
+impl Default for StateWriter {
+    fn default() -> Self {
+        StateWriter::new()
+    }
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        StateWriter { bytes: Vec::new() }
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    // length prefixed bytes
+    pub fn write_bytes(&mut self, values: &[u8]) {
+        self.write_u32(values.len() as u32);
+        self.bytes.extend_from_slice(values);
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+pub struct StateReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        StateReader { bytes, position: 0 }
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], String> {
+        if self.position + length > self.bytes.len() {
+            return Err("Save state is truncated".to_string());
+        }
+        let values = &self.bytes[self.position..self.position + length];
+        self.position += length;
+        Ok(values)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, String> {
+        let values = self.take(2)?;
+        Ok(u16::from_le_bytes([values[0], values[1]]))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, String> {
+        let values = self.take(4)?;
+        Ok(u32::from_le_bytes([values[0], values[1], values[2], values[3]]))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, String> {
+        let mut values = [0; 8];
+        values.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(values))
+    }
+
+    pub fn read_bytes(&mut self) -> Result<&'a [u8], String> {
+        let length = self.read_u32()? as usize;
+        self.take(length)
+    }
+}
+
+// serialize the complete machine
+pub fn save(cpu: &CPU, ram: &RAM, frame_buffer: &FrameBuffer) -> Vec<u8> {
+    let mut writer = StateWriter::new();
+    for value in MAGIC {
+        writer.write_u8(*value);
+    }
+    writer.write_u8(VERSION);
+    cpu.save_state(&mut writer);
+    ram.save_state(&mut writer);
+    frame_buffer.save_state(&mut writer);
+    writer.into_bytes()
+}
+
+// restore a machine from a state created by save. Nothing is changed when the state is invalid
+pub fn load(bytes: &[u8], cpu: &mut CPU, ram: &mut RAM, frame_buffer: &mut FrameBuffer) -> Result<(), String> {
+    let mut reader = StateReader::new(bytes);
+    if reader.take(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+        return Err("Not a save state".to_string());
+    }
+    let version = reader.read_u8()?;
+    if version != VERSION {
+        return Err(format!("Unsupported save state version {}", version));
+    }
+    let mut new_cpu = cpu.clone();
+    let mut new_ram = ram.clone();
+    let mut new_frame_buffer = frame_buffer.clone();
+    new_cpu.load_state(&mut reader)?;
+    new_ram.load_state(&mut reader)?;
+    new_frame_buffer.load_state(&mut reader)?;
+    *cpu = new_cpu;
+    *ram = new_ram;
+    *frame_buffer = new_frame_buffer;
+    Ok(())
+}
+
+pub fn save_to_file(path: &str, cpu: &CPU, ram: &RAM, frame_buffer: &FrameBuffer) -> Result<(), String> {
+    match fs::write(path, save(cpu, ram, frame_buffer)) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Failed to write {}: {}", path, e))
+    }
+}
+
+pub fn load_from_file(path: &str, cpu: &mut CPU, ram: &mut RAM, frame_buffer: &mut FrameBuffer) -> Result<(), String> {
+    match fs::read(path) {
+        Ok(bytes) => load(&bytes, cpu, ram, frame_buffer),
+        Err(e) => Err(format!("Failed to read {}: {}", path, e))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::{StepOutcome, CPU};
+    use crate::drivers::FrameBuffer;
+    use crate::ram::{RAM, RAM_OFFSET, XO_RAM_SIZE};
+    use crate::save_state::{load, save};
+
+    fn running_machine() -> (CPU, RAM, FrameBuffer) {
+        let mut cpu = CPU::new();
+        let mut ram = RAM::new();
+        let mut frame_buffer = FrameBuffer::new();
+        // set some registers, the timers and call a subroutine
+        let program: [u8; 12] = [
+            0x60, 0x2A,
+            0xF0, 0x15,
+            0xA3, 0x21,
+            0x22, 0x0A,
+            0x00, 0x00,
+            0x00, 0x00
+        ];
+        ram.sets(RAM_OFFSET, &program);
+        for _ in 0..4 {
+            assert_eq!(cpu.tick(&mut ram, None, None), Ok(StepOutcome::Continue));
+        }
+        frame_buffer.set_hires(true);
+        frame_buffer.draw_sprite(5, 6, &[0xFF], false);
+        (cpu, ram, frame_buffer)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let (cpu, ram, frame_buffer) = running_machine();
+        let state = save(&cpu, &ram, &frame_buffer);
+
+        let mut loaded_cpu = CPU::new();
+        let mut loaded_ram = RAM::new();
+        let mut loaded_frame_buffer = FrameBuffer::new();
+        assert_eq!(load(&state, &mut loaded_cpu, &mut loaded_ram, &mut loaded_frame_buffer), Ok(()));
+        assert_eq!(loaded_cpu.read_register(0), 0x2A);
+        assert_eq!(loaded_cpu.delay_timer(), 0x2A);
+        assert_eq!(loaded_ram.size(), ram.size());
+        assert_eq!(loaded_frame_buffer.width(), frame_buffer.width());
+        assert_eq!(loaded_frame_buffer.get(12, 6), 1);
+        assert_eq!(save(&loaded_cpu, &loaded_ram, &loaded_frame_buffer), state);
+    }
+
+    #[test]
+    fn test_load_other_memory_size() {
+        let (mut cpu, mut ram, mut frame_buffer) = running_machine();
+        let mut xo_ram = RAM::with_size(XO_RAM_SIZE);
+        let xo_state = save(&cpu, &xo_ram, &frame_buffer);
+        let before = save(&cpu, &ram, &frame_buffer);
+        assert_eq!(
+            load(&xo_state, &mut cpu, &mut ram, &mut frame_buffer).expect_err(""),
+            "Save state has 65536 bytes of memory, but the machine has 4096"
+        );
+        assert_eq!(save(&cpu, &ram, &frame_buffer), before);
+        assert_eq!(
+            load(&before, &mut cpu, &mut xo_ram, &mut frame_buffer).expect_err(""),
+            "Save state has 4096 bytes of memory, but the machine has 65536"
+        );
+    }
+
+    #[test]
+    fn test_random_numbers_continue() {
+        let mut cpu = CPU::new();
+        let mut ram = RAM::new();
+        let mut frame_buffer = FrameBuffer::new();
+        cpu.set_seed(42);
+        ram.set_u16(RAM_OFFSET, 0xC0FF);
+        ram.set_u16(RAM_OFFSET + 2, 0xC1FF);
+        cpu.tick(&mut ram, None, None).unwrap();
+        let state = save(&cpu, &ram, &frame_buffer);
+        cpu.tick(&mut ram, None, None).unwrap();
+
+        let mut loaded_cpu = CPU::new();
+        assert_eq!(load(&state, &mut loaded_cpu, &mut ram, &mut frame_buffer), Ok(()));
+        assert_eq!(loaded_cpu.seed(), 42);
+        loaded_cpu.tick(&mut ram, None, None).unwrap();
+        assert_eq!(loaded_cpu.read_register(1), cpu.read_register(1));
+    }
+
+    #[test]
+    fn test_load_invalid_state() {
+        let (mut cpu, mut ram, mut frame_buffer) = running_machine();
+        let mut state = save(&cpu, &ram, &frame_buffer);
+        let before = state.clone();
+
+        assert_eq!(load(b"nope", &mut cpu, &mut ram, &mut frame_buffer).expect_err(""), "Not a save state");
+        state[4] = 99;
+        assert_eq!(load(&state, &mut cpu, &mut ram, &mut frame_buffer).expect_err(""), "Unsupported save state version 99");
+        assert_eq!(load(&before[..before.len() - 1], &mut cpu, &mut ram, &mut frame_buffer).expect_err(""), "Save state is truncated");
+        // nothing changed
+        assert_eq!(save(&cpu, &ram, &frame_buffer), before);
+    }
+}
@@ -0,0 +1,70 @@
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::cpu::TIMER_FREQUENCY;
+
+// instructions per second, most games are written for something between 500 and 1000
+pub const DEFAULT_CLOCK_SPEED: u32 = 700;
+// the display is presented and the timers tick once per frame
+pub const FRAME_RATE: u32 = TIMER_FREQUENCY;
+
+
+pub struct Scheduler {
+    clock_speed: u32,
+    frame_duration: Duration,
+    frame: u64,
+    next_frame: Instant,
+}
+
+impl Scheduler {
+    pub fn new(clock_speed: u32) -> Self {
+        let frame_duration = Duration::from_secs(1) / FRAME_RATE;
+        Scheduler {
+            clock_speed,
+            frame_duration,
+            frame: 0,
+            next_frame: Instant::now() + frame_duration,
+        }
+    }
+
+    // number of instructions to run in the next frame. When the clock speed is not a multiple of the frame rate
+    // the remainder is spread over the frames so the average matches the clock speed exactly
+    pub fn instructions_for_frame(&mut self) -> u32 {
+        let speed = self.clock_speed as u64;
+        let frame_rate = FRAME_RATE as u64;
+        let executed = speed * self.frame / frame_rate;
+        self.frame += 1;
+        (speed * self.frame / frame_rate - executed) as u32
+    }
+
+    // sleep until it is time for the next frame
+    pub fn wait_for_next_frame(&mut self) {
+        let now = Instant::now();
+        if now < self.next_frame {
+            thread::sleep(self.next_frame - now);
+            self.next_frame += self.frame_duration;
+        } else {
+            // running behind, don't try to catch up with a burst of frames
+            self.next_frame = now + self.frame_duration;
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::scheduler::{Scheduler, FRAME_RATE};
+
+    #[test]
+    fn test_instructions_for_frame() {
+        let mut scheduler = Scheduler::new(600);
+        assert_eq!(scheduler.instructions_for_frame(), 10);
+        assert_eq!(scheduler.instructions_for_frame(), 10);
+    }
+
+    #[test]
+    fn test_instructions_for_frame_spreads_remainder() {
+        let mut scheduler = Scheduler::new(700);
+        let total: u32 = (0..FRAME_RATE).map(|_| scheduler.instructions_for_frame()).sum();
+        assert_eq!(total, 700);
+    }
+}